@@ -0,0 +1,169 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Where to keep copies of files before they are overwritten and how many of them.
+#[derive(Debug, Clone, Default)]
+pub struct Backup {
+    // Directory for all backups. If not set, backups are saved next to the file. Backups in this
+    // directory are named with a hash of the full path of the file too, so files with the same
+    // name in different directories don't share their backups.
+    pub dir: Option<PathBuf>,
+    // Maximum number of backups for each file, older ones are removed. 0 keeps all of them.
+    pub keep: usize,
+}
+
+impl Backup {
+    // Copy existing file to a new backup named after the file and current time in UTC with
+    // microseconds, such as "retroarch.cfg.reconf-20261017T120000.123456.bak", or with a
+    // directory for all backups like "roms.cfg.3f2a9c04d1e8b7a6.reconf-20261017T120000.123456.bak".
+    // Then remove oldest backups over the limit. Nothing is done if the file does not exist yet.
+    // Every call creates its own backup. If the name is taken, the time is moved forward by one
+    // microsecond, so the order of backups is kept.
+    pub fn create(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        if let Some(dir) = &self.dir {
+            fs::create_dir_all(dir)?;
+        }
+        let mut time: Duration = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let backup_path: PathBuf = loop {
+            let backup_path: PathBuf = self.directory(path).join(format!(
+                "{}{}.bak",
+                self.prefix(path),
+                timestamp(time)
+            ));
+            if !backup_path.exists() {
+                break backup_path;
+            }
+            time += Duration::from_micros(1);
+        };
+        fs::copy(path, &backup_path)?;
+        if self.keep > 0 {
+            for old in self.list(path)?.iter().skip(self.keep) {
+                fs::remove_file(old)?;
+            }
+        }
+        Ok(Some(backup_path))
+    }
+
+    // Get all backups of a file, newest first.
+    pub fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let dir: PathBuf = self.directory(path);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let prefix: String = self.prefix(path);
+        let mut backups: Vec<PathBuf> = fs::read_dir(&dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|backup| {
+                backup
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.starts_with(&prefix) && name.ends_with(".bak")
+                    })
+            })
+            .collect();
+        backups.sort();
+        backups.reverse();
+        Ok(backups)
+    }
+
+    // Find a backup of file by its number in the list, starting with 1 for the newest one, or by
+    // its path.
+    pub fn find(&self, path: &Path, backup: &str) -> io::Result<PathBuf> {
+        match backup.parse::<usize>() {
+            Ok(number) => {
+                let backups: Vec<PathBuf> = self.list(path)?;
+                number
+                    .checked_sub(1)
+                    .and_then(|index| backups.get(index))
+                    .cloned()
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!(
+                                "no backup number {number} for file: {}",
+                                path.display()
+                            ),
+                        )
+                    })
+            }
+            Err(_) => Ok(PathBuf::from(backup)),
+        }
+    }
+
+    fn directory(&self, path: &Path) -> PathBuf {
+        match (&self.dir, path.parent()) {
+            (Some(dir), _) => dir.clone(),
+            (None, Some(parent)) => parent.to_path_buf(),
+            (None, None) => PathBuf::from("."),
+        }
+    }
+
+    // Start of every backup filename for a file. In a directory shared by all files the name
+    // includes a hash of the full path, which is the same for every path to the file.
+    fn prefix(&self, path: &Path) -> String {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match &self.dir {
+            Some(_) => {
+                let full_path: PathBuf = fs::canonicalize(path)
+                    .or_else(|_| std::path::absolute(path))
+                    .unwrap_or_else(|_| path.to_path_buf());
+                format!("{name}.{:016x}.reconf-", fnv1a(&full_path))
+            }
+            None => format!("{name}.reconf-"),
+        }
+    }
+}
+
+// Hash a path with 64 bit FNV-1a, which unlike the hasher of the standard library stays the same
+// across versions of Rust, so existing backups are still found.
+// http://www.isthe.com/chongo/tech/comp/fnv/index.html
+fn fnv1a(path: &Path) -> u64 {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+// Format time in UTC as "YYYYMMDDTHHMMSS.ffffff" with microseconds, which sorts in chronological
+// order.
+fn timestamp(time: Duration) -> String {
+    let seconds: u64 = time.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time_of_day: u64 = seconds % 86400;
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}.{:06}",
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60,
+        time.subsec_micros()
+    )
+}
+
+// Convert days since 1970-01-01 into year, month and day.
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::diff::Format;
+use crate::parser::{json_string, Config};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// A problem found in a line of a config file. Line and column start at 1, column counts bytes.
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+// Read file as raw bytes and check every line for problems.
+pub fn check_file(path: &Path) -> io::Result<Vec<Issue>> {
+    let content = fs::read(path).map_err(|error| {
+        io::Error::new(error.kind(), format!("{}: {error}", path.display()))
+    })?;
+    Ok(check_bytes(&content))
+}
+
+// Check every line of content against the rules of RetroArch config format. Comments and empty
+// lines are skipped.
+pub fn check_bytes(content: &[u8]) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut first_lines: HashMap<String, usize> = HashMap::new();
+    let mut lines: Vec<&[u8]> = content.split(|&byte| byte == b'\n').collect();
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+
+    for (index, bytes) in lines.iter().enumerate() {
+        let number = index + 1;
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        let (line, offsets) = lossy_with_offsets(bytes);
+        // Report issue at a position in text of line, as column in the original bytes.
+        let mut issue =
+            |position: usize, severity: Severity, message: String| {
+                issues.push(Issue {
+                    line: number,
+                    column: offsets[position] + 1,
+                    severity,
+                    message,
+                });
+            };
+        if let Err(error) = std::str::from_utf8(bytes) {
+            issue(
+                error.valid_up_to(),
+                Severity::Error,
+                "invalid UTF-8 byte".to_string(),
+            );
+        }
+        let indent = line.len() - line.trim_start().len();
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let Some((key, _)) = Config::parse_line(&line) else {
+            issue(
+                indent,
+                Severity::Error,
+                "missing '=' between key and value".to_string(),
+            );
+            continue;
+        };
+        let equal = line.find('=').unwrap_or_default();
+        if key.is_empty() {
+            issue(equal, Severity::Error, "missing key".to_string());
+        } else if let Some(space) =
+            line[indent..equal].trim_end().find(char::is_whitespace)
+        {
+            issue(
+                indent + space,
+                Severity::Error,
+                format!("key '{key}' contains space"),
+            );
+        }
+
+        let start = equal + 1;
+        let value_part = &line[start..];
+        let value_start =
+            start + value_part.len() - value_part.trim_start().len();
+        let value_part = value_part.trim_start();
+        if let Some(quoted) = value_part.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => {
+                    let rest_start = value_start + 1 + end + 1;
+                    let rest = &line[rest_start..];
+                    let trimmed = rest.trim_start();
+                    if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                        issue(
+                            rest_start + rest.len() - trimmed.len(),
+                            Severity::Error,
+                            "trailing text after closing quotation mark"
+                                .to_string(),
+                        );
+                    }
+                }
+                None => issue(
+                    value_start,
+                    Severity::Error,
+                    "unbalanced quotation marks".to_string(),
+                ),
+            }
+        } else if let Some(second) = value_part.find('=') {
+            let full_key = line[indent..value_start + second].trim_end();
+            issue(
+                equal,
+                Severity::Error,
+                format!("key '{full_key}' contains '='"),
+            );
+        } else {
+            issue(
+                value_start,
+                Severity::Warning,
+                "value not enclosed in quotation marks".to_string(),
+            );
+            let token = value_part.split('#').next().unwrap_or_default();
+            let token_end = token.trim_end().len();
+            let first_space = token
+                .trim_end()
+                .find(char::is_whitespace)
+                .unwrap_or(token_end);
+            if first_space < token_end {
+                let rest = &token[first_space..];
+                let offset =
+                    first_space + rest.len() - rest.trim_start().len();
+                issue(
+                    value_start + offset,
+                    Severity::Error,
+                    "trailing text after value".to_string(),
+                );
+            }
+        }
+
+        match first_lines.get(key.as_str()) {
+            Some(first) => issue(
+                indent,
+                Severity::Warning,
+                format!(
+                    "duplicate key '{key}', first defined at line {first}"
+                ),
+            ),
+            None => {
+                first_lines.insert(key.to_string(), number);
+            }
+        }
+    }
+    issues
+}
+
+// Convert bytes to text like "String::from_utf8_lossy" and get the position in bytes for each
+// byte of text and for its end. Each replacement character stands for the invalid bytes.
+fn lossy_with_offsets(bytes: &[u8]) -> (String, Vec<usize>) {
+    let mut text = String::with_capacity(bytes.len());
+    let mut offsets: Vec<usize> = Vec::with_capacity(bytes.len() + 1);
+    let mut position: usize = 0;
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        offsets.extend(position..position + chunk.valid().len());
+        position += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            text.push(char::REPLACEMENT_CHARACTER);
+            offsets.extend([position; 3]);
+            position += chunk.invalid().len();
+        }
+    }
+    offsets.push(position);
+    (text, offsets)
+}
+
+// Convert issues of all files to text in requested format. Text format has one line for each
+// issue, like "file.cfg:3:12: error: message". JSON output is a list of objects.
+pub fn render(issues: &[(String, Issue)], format: Format) -> String {
+    match format {
+        Format::Json => format!(
+            "[{}]",
+            issues
+                .iter()
+                .map(|(path, issue)| format!(
+                    "{{\"file\": {}, \"line\": {}, \"column\": {}, \
+                     \"severity\": \"{}\", \"message\": {}}}",
+                    json_string(path),
+                    issue.line,
+                    issue.column,
+                    issue.severity.name(),
+                    json_string(&issue.message)
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Format::Text | Format::Unified => issues
+            .iter()
+            .map(|(path, issue)| {
+                format!(
+                    "{path}:{}:{}: {}: {}",
+                    issue.line,
+                    issue.column,
+                    issue.severity.name(),
+                    issue.message
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use std::str::FromStr;
+
+use crate::parser::{
+    format_key_string, format_pair_string, format_value_string, json_string,
+    Config,
+};
+
+// https://crates.io/crates/compact_str/
+use compact_str::CompactString;
+
+// Output format of reports, such as the differences between files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Text,
+    Unified,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(Format::Text),
+            "unified" => Ok(Format::Unified),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format '{name}', expected 'text', 'unified' or 'json'"
+            )),
+        }
+    }
+}
+
+// A single difference of one key between two configs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        key: CompactString,
+        value: CompactString,
+    },
+    Removed {
+        key: CompactString,
+        value: CompactString,
+    },
+    Changed {
+        key: CompactString,
+        old: CompactString,
+        new: CompactString,
+    },
+}
+
+// All differences between two configs, together with their paths.
+#[derive(Debug, Clone)]
+pub struct Diff {
+    pub old_path: CompactString,
+    pub new_path: CompactString,
+    pub changes: Vec<Change>,
+    // If the old file does not exist on disk.
+    pub created: bool,
+    // If the file on disk would be written with other content, even without changed keys.
+    pub rewritten: bool,
+}
+
+impl Change {
+    pub fn key(&self) -> &str {
+        match self {
+            Change::Added { key, .. }
+            | Change::Removed { key, .. }
+            | Change::Changed { key, .. } => key,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Change::Added { .. } => "added",
+            Change::Removed { .. } => "removed",
+            Change::Changed { .. } => "changed",
+        }
+    }
+}
+
+impl Diff {
+    // Compare two configs by key names. Order of keys follows the old config, followed by keys
+    // only found in new config.
+    pub fn new(old: &Config, new: &Config) -> Diff {
+        let mut changes: Vec<Change> = Vec::new();
+        for (key, value) in old.data.iter().rev() {
+            match new.data.get(key) {
+                Some(new_value) if new_value != value => {
+                    changes.push(Change::Changed {
+                        key: key.clone(),
+                        old: value.clone(),
+                        new: new_value.clone(),
+                    });
+                }
+                Some(_) => {}
+                None => changes.push(Change::Removed {
+                    key: key.clone(),
+                    value: value.clone(),
+                }),
+            }
+        }
+        for (key, value) in new.data.iter().rev() {
+            if !old.data.contains_key(key) {
+                changes.push(Change::Added {
+                    key: key.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        Diff {
+            old_path: old.path_to_string(),
+            new_path: new.path_to_string(),
+            changes,
+            created: old.path().is_some_and(|path| !path.exists()),
+            rewritten: false,
+        }
+    }
+
+    // Compare modified config with its file on disk like "new" and check if writing it would
+    // change the file. A file is rewritten if any byte differs, such as by sorting, dropped
+    // duplicate lines or another line ending. Unmodified configs are only written with "force".
+    pub fn for_write(old: &Config, new: &Config, force: bool) -> Diff {
+        let mut diff = Diff::new(old, new);
+        if force || new.needs_write() {
+            let content: Option<Vec<u8>> =
+                new.path().and_then(|path| fs::read(path).ok());
+            diff.rewritten =
+                content.is_none_or(|content| content != new.to_bytes());
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    // Number of changes by kind, such as "1 added, 0 removed, 2 changed". Files written without
+    // any changed keys are "rewritten".
+    pub fn summary(&self) -> String {
+        if self.is_empty() && !self.created {
+            return if self.rewritten {
+                "rewritten".to_string()
+            } else {
+                "unchanged".to_string()
+            };
+        }
+        let count = |kind: &str| {
+            self.changes.iter().filter(|c| c.kind() == kind).count()
+        };
+        format!(
+            "{}{} added, {} removed, {} changed",
+            if self.created { "new file, " } else { "" },
+            count("added"),
+            count("removed"),
+            count("changed")
+        )
+    }
+
+    // Human readable list of changes, one per line with the kind of change in front.
+    pub fn to_text(&self, style: u8) -> String {
+        let mut lines: Vec<String> =
+            vec![format!("{} -> {}", self.old_path, self.new_path)];
+        lines.extend(self.text_lines(style));
+        lines.join("\n")
+    }
+
+    // Human readable summary of changes for a single file, followed by the list of changes.
+    pub fn to_summary(&self, style: u8) -> String {
+        let mut lines: Vec<String> =
+            vec![format!("{}: {}", self.new_path, self.summary())];
+        lines.extend(self.text_lines(style));
+        lines.join("\n")
+    }
+
+    fn text_lines(&self, style: u8) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for change in &self.changes {
+            let line = match change {
+                Change::Added { key, value }
+                | Change::Removed { key, value } => {
+                    format_pair_string(key, value, style)
+                }
+                Change::Changed { key, old, new } => format!(
+                    "{} = \"{}\" -> \"{}\"",
+                    format_key_string(key, style),
+                    format_value_string(old, style),
+                    format_value_string(new, style)
+                ),
+            };
+            lines.push(format!("  {:<8}{line}", change.kind()));
+        }
+        lines
+    }
+
+    // Changes in the style of a unified patch, with old lines starting with "-" and new lines
+    // starting with "+".
+    pub fn to_unified(&self, style: u8) -> String {
+        let old_path: &str = if self.created {
+            "/dev/null"
+        } else {
+            &self.old_path
+        };
+        let mut lines: Vec<String> =
+            vec![format!("--- {old_path}"), format!("+++ {}", self.new_path)];
+        for change in &self.changes {
+            match change {
+                Change::Added { key, value } => {
+                    lines.push(format!(
+                        "+{}",
+                        format_pair_string(key, value, style)
+                    ));
+                }
+                Change::Removed { key, value } => {
+                    lines.push(format!(
+                        "-{}",
+                        format_pair_string(key, value, style)
+                    ));
+                }
+                Change::Changed { key, old, new } => {
+                    lines.push(format!(
+                        "-{}",
+                        format_pair_string(key, old, style)
+                    ));
+                    lines.push(format!(
+                        "+{}",
+                        format_pair_string(key, new, style)
+                    ));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    // Changes as a JSON object with paths of both files and a list of changes.
+    pub fn to_json(&self) -> String {
+        let changes: Vec<String> = self
+            .changes
+            .iter()
+            .map(|change| {
+                let values = match change {
+                    Change::Added { value, .. } => {
+                        format!("\"new\": {}", json_string(value))
+                    }
+                    Change::Removed { value, .. } => {
+                        format!("\"old\": {}", json_string(value))
+                    }
+                    Change::Changed { old, new, .. } => format!(
+                        "\"old\": {}, \"new\": {}",
+                        json_string(old),
+                        json_string(new)
+                    ),
+                };
+                format!(
+                    "{{\"type\": \"{}\", \"key\": {}, {values}}}",
+                    change.kind(),
+                    json_string(change.key())
+                )
+            })
+            .collect();
+        format!(
+            "{{\"old\": {}, \"new\": {}, \"created\": {}, \"rewritten\": {}, \
+             \"changes\": [{}]}}",
+            json_string(&self.old_path),
+            json_string(&self.new_path),
+            self.created,
+            self.rewritten,
+            changes.join(", ")
+        )
+    }
+}
+
+// Convert multiple diffs into a single text in requested format. JSON output is a list of
+// objects, one for each diff.
+pub fn render(diffs: &[Diff], format: Format, style: u8) -> String {
+    match format {
+        Format::Text => diffs
+            .iter()
+            .map(|diff| diff.to_text(style))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Format::Unified => diffs
+            .iter()
+            .map(|diff| diff.to_unified(style))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Format::Json => format!(
+            "[{}]",
+            diffs
+                .iter()
+                .map(Diff::to_json)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+// Convert diffs between files on disk and their modified data into a single text in requested
+// format. Text format has a summary line for each file.
+pub fn render_dry_run(diffs: &[Diff], format: Format, style: u8) -> String {
+    match format {
+        Format::Text => diffs
+            .iter()
+            .map(|diff| diff.to_summary(style))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => render(diffs, format, style),
+    }
+}
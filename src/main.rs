@@ -1,9 +1,20 @@
+mod backup;
+mod check;
+mod diff;
+mod migrate;
 mod parser;
+mod patch;
+mod schema;
 
-use crate::parser::Config;
+use crate::backup::Backup;
+use crate::diff::{Diff, Format};
+use crate::parser::{Config, Duplicates, Placement};
+use crate::patch::{Condition, Operation, Patch};
+use crate::schema::Schema;
 
 use std::error::Error;
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 
 // https://docs.rs/gumdrop/latest/gumdrop/
 use gumdrop::Options;
@@ -13,6 +24,10 @@ use compact_str::CompactString;
 
 const APP_VERSION: &str = "0.1";
 const APP_NAME: &str = "reconf";
+const DEFAULT_BACKUP_KEEP: usize = 5;
+const EXIT_MISSING_KEY: i32 = 2;
+const EXIT_NO_MATCH: i32 = 3;
+const EXIT_UNREADABLE: i32 = 4;
 
 #[derive(Debug, Options)]
 #[allow(clippy::struct_excessive_bools)]
@@ -42,6 +57,34 @@ struct Arguments {
     )]
     update: Vec<PathBuf>,
 
+    #[options(
+        help = "follow '#include \"FILE\"' lines in loaded files recursively,
+                       keys of the file itself have priority over included
+                       ones, changes to included keys are written back to the
+                       file defining them\n",
+        short = "I"
+    )]
+    includes: bool,
+
+    #[options(
+        help = "build the effective config RetroArch uses for core NAME, by
+                       layering the core, content directory and game overrides
+                       on top of each given main config file, such as
+                       'retroarch.cfg', and print file defining each match,
+                       the result is read only unless exported\n",
+        meta = "NAME",
+        no_short
+    )]
+    core: Option<CompactString>,
+
+    #[options(
+        help = "path of the game for option '--core', adds the content
+                       directory and game overrides to the layers\n",
+        meta = "PATH",
+        no_short
+    )]
+    content: Option<PathBuf>,
+
     #[options(
         help = "enable reading text lines from stdin to update key=value pairs
                        from, acts like an anonymous file content (edit)\n",
@@ -49,6 +92,47 @@ struct Arguments {
     )]
     stdin: bool,
 
+    #[options(
+        help = "upgrade keys RetroArch renamed, changed the meaning of values
+                       or retired in newer versions, and print each change with
+                       the version it was introduced in to stderr, combine with
+                       '--dry-run' to preview or '--write' to save (edit)\n",
+        no_short
+    )]
+    migrate: bool,
+
+    #[options(
+        help = "apply operations from patch FILE, such as the output of
+                       '--diff --format unified', lines '-key = \"old\"'
+                       remove, '+key = \"new\"' add or change after a removal
+                       line of same key and '>key = \"new_key\"' renames, each
+                       operation with a value not matching the current one is
+                       reported as conflict and skipped, then exit code is 1,
+                       option can be used multiple times (edit)\n",
+        meta = "FILE"
+    )]
+    patch: Vec<PathBuf>,
+
+    #[options(
+        help = "warn about unknown keys and invalid values from '--set',
+                       '--update' and stdin, compared to the known keys of
+                       'retroarch.cfg' in the schema, with a suggestion for
+                       misspelled keys (edit)\n",
+        no_short
+    )]
+    validate: bool,
+
+    #[options(
+        help = "load known keys with their type, allowed values, default and
+                       description from FILE instead of the bundled schema,
+                       one key per line as 'key|type|range|default|description'
+                       with type one of: 'bool', 'int', 'float', 'path', 'enum'
+                       or 'string'\n",
+        meta = "FILE",
+        no_short
+    )]
+    schema: Option<PathBuf>,
+
     #[options(
         help = "update VALUE of existing pair or insert a new KEY, option can
                        be used multiple times (edit)\n",
@@ -56,6 +140,24 @@ struct Arguments {
     )]
     set: Vec<(CompactString, CompactString)>,
 
+    #[options(
+        help = "insert KEY with VALUE only if it is missing, existing values are
+                       never changed, option can be used multiple times (edit)\n",
+        meta = "KEY VALUE",
+        no_short
+    )]
+    set_default: Vec<(CompactString, CompactString)>,
+
+    #[options(
+        help = "change value of KEY to NEW only if it currently is EXPECTED,
+                       otherwise it is reported as conflict and exit code is 1,
+                       nothing is reported if value already is NEW, option can
+                       be used multiple times (edit)\n",
+        meta = "KEY EXPECTED NEW",
+        no_short
+    )]
+    set_if: Vec<(CompactString, CompactString, CompactString)>,
+
     #[options(
         help = "search the literal text SEARCH in value of existing KEY and
                        change matching text portion with literal text REPLACE,
@@ -64,6 +166,88 @@ struct Arguments {
     )]
     replace: Vec<(CompactString, CompactString, CompactString)>,
 
+    #[options(
+        help = "search regex VALUE_PATTERN in values of all keys matching regex
+                       KEY_PATTERN and change each match with REPLACE, which
+                       can refer to capture groups like '$1', option can be
+                       used multiple times (edit)\n",
+        meta = "KEY_PATTERN VALUE_PATTERN REPLACE",
+        no_short
+    )]
+    replace_regex: Vec<(CompactString, CompactString, CompactString)>,
+
+    #[options(
+        help = "remove every key=value pair with key name matching regex
+                       PATTERN, option can be used multiple times (edit)\n",
+        meta = "PATTERN",
+        no_short
+    )]
+    delete_regex: Vec<CompactString>,
+
+    #[options(
+        help = "change name of key OLD to NEW, keeping its value and position,
+                       an existing key NEW is reported as conflict and not
+                       replaced, then exit code is 1, option can be used
+                       multiple times (edit)\n",
+        meta = "OLD NEW",
+        no_short
+    )]
+    rename: Vec<(CompactString, CompactString)>,
+
+    #[options(
+        help = "change name of every key matching regex PATTERN like
+                       '--rename', new name is the match replaced with REPLACE,
+                       which can refer to capture groups like '$1', a key whose
+                       new name is taken by another key is reported as conflict
+                       and skipped, then exit code is 1, option can be used
+                       multiple times (edit)\n",
+        meta = "PATTERN REPLACE",
+        no_short
+    )]
+    rename_regex: Vec<(CompactString, CompactString)>,
+
+    #[options(
+        help = "switch value of KEY between 'true' and 'false', option can be
+                       used multiple times (edit)\n",
+        meta = "KEY",
+        no_short
+    )]
+    toggle: Vec<CompactString>,
+
+    #[options(
+        help = "add number N to value of KEY, integers stay integers if N is a
+                       whole number, option can be used multiple times (edit)\n",
+        meta = "KEY N",
+        no_short
+    )]
+    incr: Vec<(CompactString, f64)>,
+
+    #[options(
+        help = "subtract number N from value of KEY like '--incr', option can
+                       be used multiple times (edit)\n",
+        meta = "KEY N",
+        no_short
+    )]
+    decr: Vec<(CompactString, f64)>,
+
+    #[options(
+        help = "a modifier to '--incr' and '--decr', limit results to the range
+                       from MIN to MAX (edit)\n",
+        meta = "MIN MAX",
+        no_short
+    )]
+    clamp: Option<(f64, f64)>,
+
+    #[options(
+        help = "change value of KEY to the next one in comma separated list
+                       VALUES, after the last one starts again with the first,
+                       which is also used if the current value is not in list,
+                       option can be used multiple times (edit)\n",
+        meta = "KEY VALUES",
+        no_short
+    )]
+    cycle: Vec<(CompactString, CompactString)>,
+
     #[options(
         help = "remove each key=value pair by searching exact KEY name, option
                        can be used multiple times (edit)\n",
@@ -71,6 +255,32 @@ struct Arguments {
     )]
     delete: Vec<CompactString>,
 
+    #[options(
+        help = "set existing KEY back to its default value from the schema or
+                       '--defaults', option can be used multiple times (edit)\n",
+        meta = "KEY",
+        no_short
+    )]
+    reset: Vec<CompactString>,
+
+    #[options(
+        help = "compare key names to regex PATTERN and set each match back to
+                       its default value like '--reset', option can be used
+                       multiple times (edit)\n",
+        meta = "PATTERN",
+        no_short
+    )]
+    reset_regex: Vec<CompactString>,
+
+    #[options(
+        help = "a modifier to '--reset' and '--reset-regex', remove the keys
+                       instead of setting the default value, so the value of
+                       the main config is in effect again for override files
+                       (edit)\n",
+        no_short
+    )]
+    unset: bool,
+
     #[options(
         help = "print value without quotes by searching exact KEY name, option
                        can be used multiple times (view)\n",
@@ -78,6 +288,38 @@ struct Arguments {
     )]
     get: Vec<CompactString>,
 
+    #[options(
+        help = "print VALUE for each key of '--get' which is missing (view)\n",
+        meta = "VALUE",
+        no_short
+    )]
+    default: Option<CompactString>,
+
+    #[options(
+        help = "check if exact KEY name exists in each file without printing
+                       anything, exit with code 2 if it is missing in any of
+                       them, option can be used multiple times (view)\n",
+        meta = "KEY",
+        no_short
+    )]
+    has: Vec<CompactString>,
+
+    #[options(
+        help = "exit with code 2 if a key of '--get' is missing and no
+                       '--default' is given, code 3 if '--key' or '--value'
+                       has no match and code 4 if a file cannot be read, after
+                       all files are processed (view)\n",
+        no_short
+    )]
+    strict: bool,
+
+    #[options(
+        help = "print every key found on multiple lines of a file, each line
+                       with its number, first one is in effect (view)\n",
+        no_short
+    )]
+    duplicates: bool,
+
     #[options(
         help = "compare KEY names to regex pattern and print key=value pair for
                        each match, this option is only used once, can be
@@ -103,6 +345,70 @@ struct Arguments {
     )]
     list: bool,
 
+    #[options(
+        help = "hide every key with the default value of RetroArch from
+                       '--get', '--key', '--value' and '--list', if none of
+                       them is given then all other pairs are printed, defaults
+                       are from the schema or '--defaults' (view)\n",
+        no_short
+    )]
+    non_default: bool,
+
+    #[options(
+        help = "load default values from FILE, such as a 'retroarch.cfg' of a
+                       fresh install, keys missing in FILE fall back to the
+                       default of the schema\n",
+        meta = "FILE",
+        no_short
+    )]
+    defaults: Option<PathBuf>,
+
+    #[options(
+        help = "print path of the file defining the key in front of each match
+                       from '--get', '--key' and '--value', useful with option
+                       '--includes' (view)\n",
+        no_short
+    )]
+    origin: bool,
+
+    #[options(
+        help = "print description, type, allowed values and default of each
+                       known key with name matching regex PATTERN, followed by
+                       its current value in each file, no other option is
+                       processed (view)\n",
+        meta = "PATTERN",
+        no_short
+    )]
+    explain: Option<CompactString>,
+
+    #[options(
+        help = "compare first file with each of the other files by key and
+                       print added, removed and changed keys, exit with code 1
+                       if any file differs, no other option is processed
+                       (view)\n",
+        no_short
+    )]
+    diff: bool,
+
+    #[options(
+        help = "check each file for format problems, such as lines without '=',
+                       keys with spaces, unbalanced quotation marks, trailing
+                       text after a value, duplicate keys and invalid UTF-8,
+                       print each one with 'file:line:column', exit with code 1
+                       if any is found, no other option is processed (view)\n",
+        no_short
+    )]
+    check: bool,
+
+    #[options(
+        help = "output format of reports from '--diff', '--dry-run' and
+                       '--check', accepts one of: 'text', 'unified' or 'json'
+                       (view)\n",
+        meta = "FORMAT",
+        no_short
+    )]
+    format: Option<Format>,
+
     #[options(
         help = "colorize keys and values when print to stdout, accepts a number:
                        '0'=none, '1' up to '9' are some predefined sets of color
@@ -118,7 +424,8 @@ struct Arguments {
     filenames: bool,
 
     #[options(
-        help = "sort keys for output of each file alphabetically (edit)\n",
+        help = "sort keys for output of each file alphabetically, removes
+                       comments and empty lines (edit)\n",
         short = "S"
     )]
     sort: bool,
@@ -128,6 +435,28 @@ struct Arguments {
                        saved to disk (view)\n")]
     output: bool,
 
+    #[options(
+        help = "how to handle additional lines of a key found multiple times in
+                       a file when writing, accepts one of: 'all' keeps them
+                       untouched without effect (default), 'first' removes them
+                       like RetroArch does when saving or 'error' refuses to
+                       write the file\n",
+        meta = "POLICY",
+        no_short
+    )]
+    duplicate_keys: Option<Duplicates>,
+
+    #[options(
+        help = "where to insert keys which are not in the file yet when
+                       writing, accepts one of: 'top' above all lines where
+                       RetroArch finds them first (default), 'bottom' below all
+                       lines, 'sorted' in alphabetical position among existing
+                       keys or 'after:KEY' directly below the line of KEY\n",
+        meta = "POLICY",
+        no_short
+    )]
+    placement: Option<Placement>,
+
     #[options(
         help = "force 'LF' line endings instead os default when writing a file,
                        combine it with option '--cr' to produce 'CRLF'\n",
@@ -155,6 +484,72 @@ struct Arguments {
                        original files\n")]
     write: bool,
 
+    #[options(
+        help = "remove every key=value pair with same value as in base FILE,
+                       such as 'retroarch.cfg', to keep only the differences
+                       like RetroArch does when saving overrides, if '--export'
+                       is in effect then the merged data is compared (edit)\n",
+        meta = "FILE",
+        no_short
+    )]
+    base: Option<PathBuf>,
+
+    #[options(help = "save a copy of each file before it is overwritten by
+                       '--write', named after the file and current time in UTC
+                       like 'retroarch.cfg.reconf-20261017T120000.123456.bak'\n")]
+    backup: bool,
+
+    #[options(
+        help = "save backups in directory DIR instead next to the file, with a
+                       hash of the full path of the file in their names,
+                       implies option '--backup'\n",
+        meta = "DIR",
+        no_short
+    )]
+    backup_dir: Option<PathBuf>,
+
+    #[options(
+        help = "maximum number of backups to keep for each file, older ones are
+                       removed, '0' keeps all, defaults to 5\n",
+        meta = "N",
+        no_short
+    )]
+    backup_keep: Option<usize>,
+
+    #[options(
+        help = "print numbered list of backups for each file, newest first, and
+                       exit (view)\n",
+        no_short
+    )]
+    list_backups: bool,
+
+    #[options(
+        help = "replace content of each file with a backup, given by its
+                       number from '--list-backups' or its path, before any
+                       other modification, requires '--write' option for
+                       saving to disk (edit)\n",
+        meta = "BACKUP",
+        no_short
+    )]
+    restore: Option<CompactString>,
+
+    #[options(
+        help = "write files with '--write' even if nothing was changed, by
+                       default unchanged files are left untouched on disk\n",
+        no_short
+    )]
+    force: bool,
+
+    #[options(
+        help = "process everything like '--write' would do, but instead of
+                       writing print changes for each file compared to its
+                       content on disk, including files which would be created
+                       or rewritten without changed keys, format is set by
+                       '--format'\n",
+        no_short
+    )]
+    dry_run: bool,
+
     #[options(
         help = "merge all data and write to a single file, requires --write
                        option for writing to disk (edit)",
@@ -163,7 +558,10 @@ struct Arguments {
     export: Option<CompactString>,
 }
 
-fn load_files(list_of_files: Vec<PathBuf>) -> Vec<Config> {
+fn load_files(
+    list_of_files: Vec<PathBuf>,
+    follow_includes: bool,
+) -> Vec<Config> {
     let mut source_configs: Vec<Config> = vec![];
 
     for file in list_of_files {
@@ -172,18 +570,84 @@ fn load_files(list_of_files: Vec<PathBuf>) -> Vec<Config> {
             Ok(fullpath) => fullpath,
             Err(_) => file,
         };
-        new.load(
-            &path
-                .into_os_string()
-                .into_string()
-                .expect("File path must be valid."),
-        );
+        let filename = path
+            .into_os_string()
+            .into_string()
+            .expect("File path must be valid.");
+        if follow_includes {
+            new.load_with_includes(&filename);
+        } else {
+            new.load(&filename);
+        }
         source_configs.push(new);
     }
 
     source_configs
 }
 
+// Get paths of all override files RetroArch would layer on top of main config for a core and
+// optional content, from lowest to highest priority. The main config itself is not included.
+// Overrides are found in the directory set by 'rgui_config_directory' or in "config" next to
+// main config.
+fn override_paths(
+    main_config: &Config,
+    core: &str,
+    content: Option<&PathBuf>,
+) -> Vec<PathBuf> {
+    let config_dir: PathBuf = match main_config.get("rgui_config_directory") {
+        Some(dir) if !dir.is_empty() && dir != "default" => {
+            parser::expand_home(&PathBuf::from(dir.as_str()))
+        }
+        _ => match main_config.path().as_deref().and_then(Path::parent) {
+            Some(dir) => dir.join("config"),
+            None => PathBuf::from("config"),
+        },
+    };
+    let core_dir: PathBuf = config_dir.join(core);
+    let mut paths: Vec<PathBuf> = vec![core_dir.join(format!("{core}.cfg"))];
+
+    if let Some(content) = content {
+        if let Some(name) = content
+            .parent()
+            .and_then(Path::file_name)
+            .and_then(OsStr::to_str)
+        {
+            paths.push(core_dir.join(format!("{name}.cfg")));
+        }
+        if let Some(name) = content.file_stem().and_then(OsStr::to_str) {
+            paths.push(core_dir.join(format!("{name}.cfg")));
+        }
+    }
+
+    paths
+}
+
+// Merge main config with all existing override files for the core, the way RetroArch does when
+// starting a game. Each layer is remembered to find out which one defines a key.
+fn load_effective(
+    main_config: Config,
+    core: &str,
+    content: Option<&PathBuf>,
+    follow_includes: bool,
+) -> Config {
+    let paths: Vec<PathBuf> = override_paths(&main_config, core, content)
+        .into_iter()
+        .filter(|path| path.is_file())
+        .collect();
+    let mut layers: Vec<Config> = vec![main_config];
+    layers.extend(load_files(paths, follow_includes));
+
+    let mut effective = Config::new();
+    for layer in &layers {
+        effective.insert_from_config(layer);
+    }
+    // Effective config is incomplete, if any of its files could not be read.
+    effective.unreadable = layers.iter().any(|layer| layer.unreadable);
+    layers.reverse();
+    effective.layers = layers;
+    effective
+}
+
 fn read_stdin_config() -> Config {
     use std::io::prelude::*;
     let stdin = std::io::stdin();
@@ -197,6 +661,8 @@ fn read_stdin_config() -> Config {
     for line in lines {
         stdin_config.insert_from_string(&line);
     }
+    // Data is stored in reverse order of lines, like in a file read from disk.
+    stdin_config.data.reverse();
 
     stdin_config
 }
@@ -223,6 +689,8 @@ values without complicated commands or escaping quotation marks. When multiple
 options are present, then following priority is processed in order:
 
     1. Read editable files. In example: '{APP_NAME} file1.cfg'
+       Content is replaced by a backup, if option '--restore' is given.
+       Keys of older RetroArch versions are upgraded with '--migrate'.
     2. Load update files and apply them to parts of editable files.
     3. Read in stdin and apply content to parts of editable files.
     4. Apply content editing commands such as '--set' or '--replace'.
@@ -252,8 +720,9 @@ the terminal. The following lines are output.
 
 Surrounding quotation marks on values are handled automatically when reading or
 writing. If a key is found multiple times with different values in a file, then
-it priotizes the top most, just like RetroArch does. But this program will
-remove the other instances of the key when saving. Comments are also removed.
+it priotizes the top most, just like RetroArch does. Lines which are not
+changed, such as comments, empty lines and other instances of the key, are kept
+as they are when saving.
 
     # Read a file and rewrite it to disk after default processing.
     $ {APP_NAME} file1.cfg -w
@@ -337,8 +806,8 @@ keys on the fly and apply it to the editable files or output stream.
     video_scale_integer
 
 When inserting new keys or reading them, it will always insert to or operate on
-the top. RetroArch priotizes first encounter of key too. Have in mind this
-program do not preserve comments in the config file.
+the top. RetroArch priotizes first encounter of key too. Sorting the file
+with '--sort' will drop comments and empty lines.
 
 https://github.com/thingsiplay/{APP_NAME}/"
     )
@@ -368,22 +837,228 @@ fn main() -> Result<(), Box<dyn Error>> {
     // commandline options. Create a dummy file and the export if necessary.
     // All data from these files is then collected for interpretation as
     // RetroArch .cfg config data.
-    let mut source_configs: Vec<Config> = load_files(args.file);
+    let mut source_configs: Vec<Config> = load_files(args.file, args.includes);
     if source_configs.is_empty() {
         source_configs.push(Config::new());
     }
-    let mut update_configs: Vec<Config> = load_files(args.update);
+    if let Some(ref core) = args.core {
+        source_configs = source_configs
+            .into_iter()
+            .map(|main_config| {
+                load_effective(
+                    main_config,
+                    core,
+                    args.content.as_ref(),
+                    args.includes,
+                )
+            })
+            .collect();
+    }
+    let show_origin: bool = args.origin || args.core.is_some();
+    let backup: Backup = Backup {
+        dir: args.backup_dir.clone(),
+        keep: args.backup_keep.unwrap_or(DEFAULT_BACKUP_KEEP),
+    };
+
+    let schema: Schema = match args.schema {
+        Some(ref path) => Schema::load(path)?,
+        None => Schema::bundled(),
+    };
+
+    // List backups of files only and exit, without any further processing.
+    if args.list_backups {
+        for config in &source_configs {
+            let Some(path) = config.path() else {
+                continue;
+            };
+            if args.filenames {
+                println!();
+                eprintln!("{}:", config.path_to_string());
+            }
+            for (index, file) in backup.list(&path)?.iter().enumerate() {
+                println!("{}: {}", index + 1, file.display());
+            }
+        }
+        return Ok(());
+    }
+
+    // Check files only and exit, without any further processing.
+    if args.check {
+        let mut issues: Vec<(String, check::Issue)> = Vec::new();
+        for config in &source_configs {
+            let Some(path) = config.path() else {
+                continue;
+            };
+            let filename: String = config.path_to_string().to_string();
+            for issue in check::check_file(&path)? {
+                issues.push((filename.clone(), issue));
+            }
+        }
+        let report: String =
+            check::render(&issues, args.format.unwrap_or_default());
+        if !report.is_empty() {
+            println!("{report}");
+        }
+        if !issues.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Document keys only and exit, without any further processing.
+    if let Some(ref pattern) = args.explain {
+        let entries: Vec<&schema::Entry> = schema.find(pattern);
+        if entries.is_empty() {
+            eprintln!("Warning! No known key matches: {pattern}");
+        }
+        for entry in entries {
+            println!("{}\n", entry.explain(&source_configs, args.color));
+        }
+        return Ok(());
+    }
+
+    // Compare files only and exit, without any further processing.
+    if args.diff {
+        let diffs: Vec<Diff> = source_configs
+            .iter()
+            .skip(1)
+            .map(|config| Diff::new(&source_configs[0], config))
+            .collect();
+        println!(
+            "{}",
+            diff::render(&diffs, args.format.unwrap_or_default(), args.color)
+        );
+        if diffs.iter().any(|diff| !diff.is_empty()) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let mut update_configs: Vec<Config> =
+        load_files(args.update, args.includes);
     let mut export_config: Config = Config::new();
     if let Some(ref path) = args.export {
         export_config.set_path(path);
     }
+    if let Some(ref placement) = args.placement {
+        export_config.placement = placement.clone();
+    }
     if args.stdin {
         update_configs.push(read_stdin_config());
     }
+    let set_values: Vec<(&CompactString, &CompactString)> = args
+        .set
+        .iter()
+        .chain(&args.set_default)
+        .map(|(key, value)| (key, value))
+        .chain(args.set_if.iter().map(|(key, _, value)| (key, value)))
+        .collect();
+    if let Some((key, _)) = set_values
+        .iter()
+        .find(|(_, value)| !parser::is_representable(value))
+    {
+        return Err(format!(
+            "Value for key '{key}' can't be saved, quotation marks are not \
+             allowed together with spaces or '#'"
+        )
+        .into());
+    }
+    if args.validate {
+        for (key, value) in &set_values {
+            if let Err(message) = schema.validate(key, value) {
+                eprintln!("Warning! --set: {message}");
+            }
+        }
+        for update in &update_configs {
+            let source: CompactString = match update.path {
+                Some(_) => update.path_to_string(),
+                None => "stdin".into(),
+            };
+            for (key, value) in update.data.iter().rev() {
+                if let Err(message) = schema.validate(key, value) {
+                    eprintln!("Warning! {source}: {message}");
+                }
+            }
+        }
+    }
+    let mut defaults: Config = schema.defaults();
+    if let Some(path) = args.defaults {
+        defaults.insert_from_config(&load_files(vec![path], false).remove(0));
+    }
+    // Option '--non-default' alone prints all pairs, as if every key matches.
+    let key_pattern: Option<CompactString> = match args.key {
+        None if args.non_default
+            && args.value.is_none()
+            && args.get.is_empty()
+            && !args.list =>
+        {
+            Some(CompactString::from(""))
+        }
+        key => key,
+    };
+    let patches: Vec<Patch> = args
+        .patch
+        .iter()
+        .map(|path| Patch::load(path))
+        .collect::<Result<_, _>>()?;
+    // Conditional changes from '--set-if' work like a patch, with conflicts
+    // reported the same way.
+    let set_if: Patch = Patch {
+        path: None,
+        operations: args
+            .set_if
+            .iter()
+            .map(|(key, expected, value)| Operation::Set {
+                key: key.clone(),
+                value: value.clone(),
+                condition: Condition::Equals(expected.clone()),
+            })
+            .collect(),
+    };
+    let base_config: Option<Config> = args
+        .base
+        .map(|path| load_files(vec![path], args.includes).remove(0));
+
+    // Keep files as they are on disk, to compare with the modified data.
+    let originals: Vec<Config> = if args.dry_run {
+        source_configs.clone()
+    } else {
+        Vec::new()
+    };
+    let mut dry_run_diffs: Vec<Diff> = Vec::new();
 
     // Process all input files, update commands and print if requested.
-    for config in &mut source_configs {
+    let mut exit_code: i32 = 0;
+    for (index, config) in source_configs.iter_mut().enumerate() {
         config.style = args.color;
+        if args.backup || args.backup_dir.is_some() {
+            config.backup = Some(backup.clone());
+        }
+        if let Some(policy) = args.duplicate_keys {
+            config.set_duplicates(policy);
+        }
+        if let Some(ref placement) = args.placement {
+            config.placement = placement.clone();
+        }
+
+        // Restore from backup
+        if let (Some(name), Some(path)) = (&args.restore, config.path()) {
+            let mut restored = Config::new();
+            restored.load(&backup.find(&path, name)?.to_string_lossy());
+            restored.set_path(&config.path_to_string());
+            restored.style = config.style;
+            restored.backup = config.backup.take();
+            restored.duplicates = config.duplicates;
+            restored.placement = config.placement.clone();
+            restored.modified = true;
+            *config = restored;
+        }
+
+        // Migrate keys of older RetroArch versions
+        if args.migrate {
+            for message in migrate::migrate(config) {
+                eprintln!("{}: {message}", config.path_to_string());
+            }
+        }
 
         // Updating commands
         for update in &update_configs {
@@ -391,28 +1066,143 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
 
         // Editing commands
+        for patch in &patches {
+            for conflict in patch.apply(config) {
+                eprintln!("Conflict! {}: {conflict}", config.path_to_string());
+                exit_code = exit_code.max(1);
+            }
+        }
         if args.sort && args.export.is_none() {
             config.sort();
         }
+        for (key, new_key) in &args.rename {
+            if let Err(conflict) = config.rename(key, new_key) {
+                eprintln!("Conflict! {}: {conflict}", config.path_to_string());
+                exit_code = exit_code.max(1);
+            }
+        }
+        for (pattern, replace) in &args.rename_regex {
+            for conflict in config.rename_regex(pattern, replace) {
+                eprintln!("Conflict! {}: {conflict}", config.path_to_string());
+                exit_code = exit_code.max(1);
+            }
+        }
         for (key, value) in &args.set {
             config.set(key, value);
         }
+        for (key, value) in &args.set_default {
+            config.add(key, value);
+        }
+        for conflict in set_if.apply(config) {
+            eprintln!("Conflict! {}: {conflict}", config.path_to_string());
+            exit_code = exit_code.max(1);
+        }
         for (key, search, replace) in &args.replace {
             config.replace(key, search, replace);
         }
+        for (k_pattern, v_pattern, replace) in &args.replace_regex {
+            config.replace_regex(k_pattern, v_pattern, replace);
+        }
+        // Missing keys for '--toggle', '--incr' and '--decr' start with their
+        // default value.
+        for key in args
+            .toggle
+            .iter()
+            .chain(args.incr.iter().chain(&args.decr).map(|(key, _)| key))
+        {
+            if let (false, Some(value)) =
+                (config.has_key(key), defaults.get(key))
+            {
+                config.set(key, &value);
+            }
+        }
+        let mut errors: Vec<parser::ValueError> = Vec::new();
+        for key in &args.toggle {
+            errors.extend(config.toggle(key).err());
+        }
+        let (min, max) = args.clamp.unzip();
+        for (key, amount) in &args.incr {
+            errors.extend(config.increment(key, *amount, min, max).err());
+        }
+        for (key, amount) in &args.decr {
+            errors.extend(config.increment(key, -amount, min, max).err());
+        }
+        for (key, values) in &args.cycle {
+            config.cycle(key, &values.split(',').collect::<Vec<&str>>());
+        }
+        for error in errors {
+            eprintln!("Error! {}: {error}", config.path_to_string());
+            exit_code = exit_code.max(1);
+        }
         for key in &args.delete {
             config.remove(key);
         }
+        for pattern in &args.delete_regex {
+            config.remove_regex(pattern);
+        }
+        let mut reset_keys: Vec<CompactString> = args.reset.clone();
+        for pattern in &args.reset_regex {
+            reset_keys.extend(
+                config.find_by_key(pattern).into_iter().map(|(k, _)| k),
+            );
+        }
+        for key in &reset_keys {
+            if args.unset {
+                config.remove(key);
+            } else if !config.reset(key, &defaults) {
+                eprintln!(
+                    "Warning! {}: no default value known for key '{key}'",
+                    config.path_to_string()
+                );
+            }
+        }
+        if let (Some(base), None) = (&base_config, &args.export) {
+            config.remove_equal(base);
+        }
 
         // Viewing commands
+        // With '--non-default' all of them only see keys with a value other
+        // than the default.
+        let non_default: Config;
+        let view: &Config = if args.non_default {
+            non_default = {
+                let mut filtered: Config = config.clone();
+                filtered.remove_same(&defaults, |key, value, default| {
+                    match schema.get(key) {
+                        Some(entry) => entry.same_value(value, default),
+                        None => value == default,
+                    }
+                });
+                filtered
+            };
+            &non_default
+        } else {
+            config
+        };
         if args.filenames {
             println!();
-            eprintln!("{}:", config.path_to_string());
+            eprintln!("{}:", view.path_to_string());
+        }
+        if args.strict && config.unreadable {
+            exit_code = exit_code.max(EXIT_UNREADABLE);
+        }
+        if args.has.iter().any(|key| !config.has_key(key)) {
+            exit_code = exit_code.max(EXIT_MISSING_KEY);
+        }
+        if args.duplicates {
+            view.print_duplicates();
         }
         for key in &args.get {
-            if let Some(value) = config.get(key) {
-                config.print_value(&value);
-            };
+            if let Some(value) = view.get(key) {
+                if show_origin {
+                    view.print_origin(key);
+                }
+                view.print_value(&value);
+            } else if let Some(ref default) = args.default {
+                view.print_value(default);
+            } else if args.strict {
+                exit_code = exit_code.max(EXIT_MISSING_KEY);
+            }
         }
 
         // The following segment has 2 purposes: If key and value are given at
@@ -423,39 +1213,58 @@ fn main() -> Result<(), Box<dyn Error>> {
         //
         // Mode: Key and Value +- List
         // if args.key.is_some() && args.value.is_some() {
-        if let (Some(k), Some(v)) = (&args.key, &args.value) {
-            for (key, value) in config.find(k, v) {
+        let mut matches: usize = 0;
+        if let (Some(k), Some(v)) = (&key_pattern, &args.value) {
+            for (key, value) in view.find(k, v) {
+                matches += 1;
+                if show_origin {
+                    view.print_origin(&key);
+                }
                 if args.list {
-                    config.print_key(&key);
-                    config.print_value(&value);
+                    view.print_key(&key);
+                    view.print_value(&value);
                 } else {
-                    config.print_pair(&key, &value);
+                    view.print_pair(&key, &value);
                 }
             }
         // Mode: List only
-        } else if args.list && args.key.is_none() && args.value.is_none() {
-            config.print_keys_list();
+        } else if args.list && key_pattern.is_none() && args.value.is_none() {
+            view.print_keys_list();
         // Mode: Key or Value +- List
         } else {
-            if let Some(k_pattern) = &args.key {
-                for (key, value) in config.find_by_key(k_pattern) {
+            if let Some(k_pattern) = &key_pattern {
+                for (key, value) in view.find_by_key(k_pattern) {
+                    matches += 1;
+                    if show_origin {
+                        view.print_origin(&key);
+                    }
                     if args.list {
-                        config.print_key(&key);
+                        view.print_key(&key);
                     } else {
-                        config.print_pair(&key, &value);
+                        view.print_pair(&key, &value);
                     }
                 }
             }
             if let Some(v_pattern) = &args.value {
-                for (key, value) in config.find_by_value(v_pattern) {
+                for (key, value) in view.find_by_value(v_pattern) {
+                    matches += 1;
+                    if show_origin {
+                        view.print_origin(&key);
+                    }
                     if args.list {
-                        config.print_value(&value);
+                        view.print_value(&value);
                     } else {
-                        config.print_pair(&key, &value);
+                        view.print_pair(&key, &value);
                     }
                 }
             }
         }
+        if args.strict
+            && (key_pattern.is_some() || args.value.is_some())
+            && matches == 0
+        {
+            exit_code = exit_code.max(EXIT_NO_MATCH);
+        }
 
         // Writing commands
         // Overwrite files with '--write' only if no '--export' option is set.
@@ -465,11 +1274,33 @@ fn main() -> Result<(), Box<dyn Error>> {
             if args.output {
                 println!("{}", config);
             }
-            if args.write {
+            if (args.write || args.dry_run) && args.core.is_some() {
+                eprintln!(
+                    "Warning! Effective config is read only, use '--export' \
+                     for saving it to a file."
+                );
+            } else if args.dry_run || args.write {
+                // Dry run applies the same settings as writing, to compare the
+                // exact content of file.
                 if let Some(newline) = force_newline {
-                    config.lineending = newline;
+                    config.set_lineending(newline);
+                }
+                if args.dry_run {
+                    dry_run_diffs.push(Diff::for_write(
+                        &originals[index],
+                        config,
+                        args.force,
+                    ));
+                } else if args.force || config.needs_write() {
+                    // Report failed files and continue with the next one.
+                    if let Err(error) = config.write(args.force) {
+                        eprintln!(
+                            "Error! {}: {error}",
+                            config.path_to_string()
+                        );
+                        exit_code = exit_code.max(1);
+                    }
                 }
-                config.write()?;
             }
         }
     }
@@ -482,19 +1313,54 @@ fn main() -> Result<(), Box<dyn Error>> {
             println!();
             eprintln!("{}:", export_config.path_to_string());
         }
+        if let Some(ref base) = base_config {
+            export_config.remove_equal(base);
+        }
         if args.sort {
             export_config.sort();
         }
         if args.output {
             println!("{}", export_config);
         }
-        if args.write {
-            if let Some(newline) = force_newline {
-                export_config.lineending = newline;
+        if let Some(newline) = force_newline {
+            export_config.lineending = newline;
+        }
+        if args.dry_run {
+            let mut original: Config = Config::new();
+            original.set_path(&export_config.path_to_string());
+            if original.path().is_some_and(|path| path.is_file()) {
+                original.read_file();
+            }
+            // Export file is always written.
+            dry_run_diffs.push(Diff::for_write(
+                &original,
+                &export_config,
+                true,
+            ));
+        } else if args.write {
+            if let Err(error) = export_config.write(args.force) {
+                eprintln!(
+                    "Error! {}: {error}",
+                    export_config.path_to_string()
+                );
+                exit_code = exit_code.max(1);
             }
-            export_config.write()?;
         }
     }
 
+    if args.dry_run {
+        println!(
+            "{}",
+            diff::render_dry_run(
+                &dry_run_diffs,
+                args.format.unwrap_or_default(),
+                args.color
+            )
+        );
+    }
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
     Ok(())
 }
@@ -0,0 +1,125 @@
+use crate::parser::Config;
+
+// A single change RetroArch made to a key of its config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    // Key was renamed, the value is kept.
+    Rename {
+        key: &'static str,
+        new_key: &'static str,
+    },
+    // A value of the key means something else now and is replaced.
+    Transform {
+        key: &'static str,
+        old: &'static str,
+        new: &'static str,
+    },
+    // Key has no effect anymore.
+    Remove {
+        key: &'static str,
+    },
+}
+
+// A rule together with the version of RetroArch it was introduced in.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: &'static str,
+    pub rule: Rule,
+}
+
+// Known changes of keys, oldest first. Each rule only applies to configs still in the old state,
+// so the table can be applied to any config any number of times.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: "1.7.0",
+        rule: Rule::Remove {
+            key: "netplay_mode",
+        },
+    },
+    Migration {
+        version: "1.9.1",
+        rule: Rule::Rename {
+            key: "core_updater_buildbot_url",
+            new_key: "core_updater_buildbot_cores_url",
+        },
+    },
+    Migration {
+        version: "1.10.0",
+        rule: Rule::Rename {
+            key: "netplay_spectator_mode_enable",
+            new_key: "netplay_start_as_spectator",
+        },
+    },
+    Migration {
+        version: "1.10.0",
+        rule: Rule::Transform {
+            key: "video_black_frame_insertion",
+            old: "true",
+            new: "1",
+        },
+    },
+    Migration {
+        version: "1.10.0",
+        rule: Rule::Transform {
+            key: "video_black_frame_insertion",
+            old: "false",
+            new: "0",
+        },
+    },
+    Migration {
+        version: "1.11.0",
+        rule: Rule::Remove {
+            key: "netplay_stateless_mode",
+        },
+    },
+    Migration {
+        version: "1.11.0",
+        rule: Rule::Remove {
+            key: "netplay_client_swap_input",
+        },
+    },
+];
+
+impl Migration {
+    // Apply rule to config, if it is still in the old state. Returns a description of the change,
+    // if anything was changed.
+    pub fn apply(&self, config: &mut Config) -> Option<String> {
+        let message = match self.rule {
+            Rule::Rename { key, new_key } => {
+                match config.rename(key, new_key) {
+                    Ok(value) => format!(
+                        "renamed '{key}' to '{new_key}' = \"{}\"",
+                        value?
+                    ),
+                    Err(_) => {
+                        config.remove(key);
+                        format!(
+                            "removed '{key}', replaced by existing key \
+                             '{new_key}'"
+                        )
+                    }
+                }
+            }
+            Rule::Transform { key, old, new } => {
+                if config.get(key)? != old {
+                    return None;
+                }
+                config.set(key, new);
+                format!("changed '{key}' from \"{old}\" to \"{new}\"")
+            }
+            Rule::Remove { key } => {
+                let value = config.remove(key)?;
+                format!("removed '{key}' = \"{value}\", it has no effect")
+            }
+        };
+        Some(format!("{}: {message}", self.version))
+    }
+}
+
+// Apply all known migrations to config in order and get a description of each change.
+pub fn migrate(config: &mut Config) -> Vec<String> {
+    MIGRATIONS
+        .iter()
+        .filter_map(|migration| migration.apply(config))
+        .collect()
+}
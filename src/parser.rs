@@ -1,10 +1,14 @@
 #![allow(dead_code)]
 
+use std::collections::HashSet;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
 //use std::io::{self, BufRead, Write};
 use std::io::{self, Write};
 use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::backup::Backup;
 
 // use core::iter::Rev;
 
@@ -14,9 +18,6 @@ use indexmap::IndexMap;
 // https://docs.rs/regex/latest/regex/
 use regex::Regex;
 
-// https://docs.rs/rev_lines/latest/rev_lines/
-use rev_lines::RevLines;
-
 // https://crates.io/crates/compact_str/
 use compact_str::CompactString;
 use compact_str::ToCompactString;
@@ -30,7 +31,117 @@ const NL: &str = "\r\n";
 #[cfg(not(windows))]
 const NL: &str = "\n";
 
-#[derive(Debug, Default)]
+// A single line of a .cfg file as it was read from disk. Used to write the file back without
+// losing comments, empty lines, duplicate keys or the original order. The raw bytes are kept as
+// they are, even if they are not valid UTF-8.
+#[derive(Debug, Clone)]
+pub enum Line {
+    // Key value pair with its parsed parts and the original unmodified bytes.
+    Pair {
+        key: CompactString,
+        value: CompactString,
+        raw: Vec<u8>,
+    },
+    // Comment, empty line or any other text which is not a key value pair.
+    Other(Vec<u8>),
+}
+
+// How to handle lines with a key, which was already found further up in the same file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicates {
+    // Keep the lines untouched when writing, but without any effect.
+    #[default]
+    KeepAll,
+    // Remove the lines when writing, like RetroArch does when saving.
+    KeepFirst,
+    // Refuse to write the file.
+    Error,
+}
+
+impl FromStr for Duplicates {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "all" => Ok(Duplicates::KeepAll),
+            "first" => Ok(Duplicates::KeepFirst),
+            "error" => Ok(Duplicates::Error),
+            _ => Err(format!(
+                "unknown policy '{name}', expected 'all', 'first' or 'error'"
+            )),
+        }
+    }
+}
+
+// Where keys which are not in the file yet are inserted when writing.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Placement {
+    // Above all other lines, where RetroArch finds them first.
+    #[default]
+    Top,
+    // Below all other lines, in order they were added.
+    Bottom,
+    // Above the first key with a name sorting after the new one, or below the last key.
+    Sorted,
+    // Directly below the line of given key, or at top if the key is not in the file.
+    After(CompactString),
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "top" => Ok(Placement::Top),
+            "bottom" => Ok(Placement::Bottom),
+            "sorted" => Ok(Placement::Sorted),
+            _ => match name.strip_prefix("after:") {
+                Some(key) if !key.is_empty() => {
+                    Ok(Placement::After(key.to_compact_string()))
+                }
+                _ => Err(format!(
+                    "unknown placement '{name}', expected 'top', 'bottom', \
+                     'sorted' or 'after:KEY'"
+                )),
+            },
+        }
+    }
+}
+
+// Reason a value could not be read as a specific type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueError {
+    // Key does not exist.
+    Missing {
+        key: CompactString,
+    },
+    // Value cannot be converted to the requested type.
+    Invalid {
+        key: CompactString,
+        value: CompactString,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueError::Missing { key } => write!(f, "key '{key}' not found"),
+            ValueError::Invalid {
+                key,
+                value,
+                expected,
+            } => write!(
+                f,
+                "key '{key}' has invalid value \"{value}\", expected {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+#[derive(Debug, Default, Clone)]
 pub struct Config {
     // Used for reading from and writing to .cfg files.
     pub path: Option<PathBuf>,
@@ -41,24 +152,40 @@ pub struct Config {
     // Controls on values if surrounding quotation marks should be automatically removed and added
     // when reading or writing. (experimental)
     pub data: IndexMap<CompactString, CompactString>,
+    // Every line of the file in original order, as it was read from disk. New keys which are not
+    // part of these lines are added to the top when converting to text.
+    pub lines: Vec<Line>,
+    // If the file ended with a newline character when it was read.
+    pub final_newline: bool,
+    // Files loaded from "#include" lines in order of appearance. Their keys are merged into data,
+    // but keys of the file itself have priority.
+    pub includes: Vec<Config>,
+    // Configs the data was merged from, with highest priority first. Only used to look up the
+    // origin of keys, these are never written.
+    pub layers: Vec<Config>,
+    // If set, a copy of the file is saved before it is overwritten.
+    pub backup: Option<Backup>,
+    // If data was changed since the file was read.
+    pub modified: bool,
+    // What to do with lines of keys found multiple times in the file.
+    pub duplicates: Duplicates,
+    // Where new keys are inserted into the lines of the file.
+    pub placement: Placement,
+    // If the file at path could not be read, because it is missing, a directory or for any
+    // other reason.
+    pub unreadable: bool,
+    // Keys added to data after reading the file, oldest first. Data itself is stored in reverse
+    // order of the file, so it can't tell the order keys were added in.
+    pub added: Vec<CompactString>,
 }
 
 // Convert internal data to String representation.
 impl fmt::Display for Config {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let data_as_string: String = self
-            .data
-            .iter()
-            .rev()
-            .map(|(key, value)| {
-                format!(
-                    "{} = \"{}\"",
-                    format_key_string(key, self.style),
-                    format_value_string(value, self.style),
-                )
-            })
-            .collect::<Vec<_>>()
-            .join(self.lineending);
+        let lines: Vec<Vec<u8>> = self.render_lines();
+        let data_as_string: String =
+            String::from_utf8_lossy(&lines.join(self.lineending.as_bytes()))
+                .to_string();
         write!(f, "{}", data_as_string)?;
         Ok(())
     }
@@ -71,6 +198,16 @@ impl Config {
             style: 0,
             lineending: NL,
             data: IndexMap::new(),
+            lines: Vec::new(),
+            final_newline: false,
+            includes: Vec::new(),
+            layers: Vec::new(),
+            backup: None,
+            modified: false,
+            duplicates: Duplicates::KeepAll,
+            placement: Placement::Top,
+            unreadable: false,
+            added: Vec::new(),
         }
     }
 
@@ -80,15 +217,193 @@ impl Config {
         self.read_file();
     }
 
-    // Overwrite file at path with current data converted to cfg text format.
-    pub fn write(&self) -> io::Result<()> {
+    // Set path by slice and read file into data, then follow each "#include" line recursively.
+    // Included files are loaded and their keys are added to data, if not already set by the
+    // file itself. A file including itself directly or indirectly is skipped with a warning.
+    pub fn load_with_includes(&mut self, filename: &str) {
+        self.load_recursive(filename, &mut Vec::new());
+    }
+
+    fn load_recursive(&mut self, filename: &str, parents: &mut Vec<PathBuf>) {
+        self.load(filename);
+        let current = match self.path.as_ref().map(fs::canonicalize) {
+            Some(Ok(path)) => path,
+            _ => return,
+        };
+        parents.push(current.clone());
+        for include in self.list_includes() {
+            let path = match current.parent() {
+                Some(directory) => normalize_path(&directory.join(include)),
+                None => include,
+            };
+            if parents.iter().any(|parent| {
+                Some(parent) == path.canonicalize().ok().as_ref()
+            }) {
+                eprintln!(
+                    "Warning! Skip include cycle: \"{}\" included by \"{}\"",
+                    path.display(),
+                    current.display()
+                );
+                continue;
+            }
+            let mut config = Config::new();
+            config.load_recursive(&path.to_string_lossy(), parents);
+            self.includes.push(config);
+        }
+        parents.pop();
+
+        for config in &self.includes {
+            for (key, value) in &config.data {
+                if !self.data.contains_key(key) {
+                    self.data.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    // Get paths of all "#include" lines in order of appearance, with home directory expanded.
+    pub fn list_includes(&self) -> Vec<PathBuf> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Other(raw) => {
+                    parse_include(&String::from_utf8_lossy(raw))
+                }
+                Line::Pair { .. } => None,
+            })
+            .map(|path| expand_home(&path))
+            .collect()
+    }
+
+    // Get path of the file which defines given key. Keys of the file itself have priority over
+    // included files and layers. Keys not found in any file belong to the file itself, if set.
+    pub fn origin(&self, key: &str) -> Option<PathBuf> {
+        if self.has_own_line(key) {
+            return self.path();
+        }
+        match self
+            .includes
+            .iter()
+            .chain(&self.layers)
+            .find(|config| config.has_key(key))
+        {
+            Some(config) => config.origin(key),
+            None if self.has_key(key) => self.path(),
+            None => None,
+        }
+    }
+
+    // Check if key is written in one of the lines of the file itself, excluding includes.
+    fn has_own_line(&self, key: &str) -> bool {
+        self.lines.iter().any(|line| line.key() == Some(key))
+    }
+
+    // Apply current data to the included files, for all keys they define. Keys removed from data
+    // are removed from the included file too.
+    fn updated_includes(&self) -> Vec<Config> {
+        let mut includes = self.includes.clone();
+        for include in &mut includes {
+            include.backup = self.backup.clone();
+        }
+        for index in 0..includes.len() {
+            let keys: Vec<CompactString> = includes[index].list_keys();
+            for key in keys {
+                let owned_by_other = self.has_own_line(&key)
+                    || includes[..index].iter().any(|c| c.has_key(&key));
+                if owned_by_other {
+                    continue;
+                }
+                let include = &mut includes[index];
+                match self.data.get(&key) {
+                    Some(value)
+                        if include.get(&key).as_ref() != Some(value) =>
+                    {
+                        include.set(&key, value);
+                    }
+                    Some(_) => {}
+                    None => {
+                        include.remove(&key);
+                    }
+                }
+            }
+        }
+        includes
+    }
+
+    // Check if the file on disk is out of date, because data was modified since reading it or
+    // the file does not exist yet.
+    pub fn needs_write(&self) -> bool {
+        self.modified || self.path.as_ref().is_some_and(|path| !path.exists())
+    }
+
+    // Change how lines with keys found multiple times are handled when writing.
+    pub fn set_duplicates(&mut self, duplicates: Duplicates) {
+        if duplicates == Duplicates::KeepFirst
+            && self.duplicates != Duplicates::KeepFirst
+            && !self.find_duplicates().is_empty()
+        {
+            self.modified = true;
+        }
+        self.duplicates = duplicates;
+    }
+
+    // Get every key found on multiple lines of the file, together with line numbers and values
+    // of all its occurrences. The first occurrence is the one in effect.
+    pub fn find_duplicates(
+        &self,
+    ) -> Vec<(CompactString, Vec<(usize, CompactString)>)> {
+        let mut occurrences: IndexMap<
+            CompactString,
+            Vec<(usize, CompactString)>,
+        > = IndexMap::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            if let Line::Pair { key, value, .. } = line {
+                occurrences
+                    .entry(key.clone())
+                    .or_default()
+                    .push((index + 1, value.clone()));
+            }
+        }
+        occurrences
+            .into_iter()
+            .filter(|(_, lines)| lines.len() > 1)
+            .collect()
+    }
+
+    // Change line ending used for writing the file.
+    pub fn set_lineending(&mut self, lineending: &'static str) {
+        if self.lineending != lineending {
+            self.lineending = lineending;
+            self.modified = true;
+        }
+    }
+
+    // Overwrite file at path with current data converted to cfg text format. Changed keys which
+    // are defined in included files are written back to those files. A file with the exact same
+    // content on disk is left untouched, including its modification time, unless forced.
+    pub fn write(&self, force: bool) -> io::Result<()> {
+        // Check everything first, so nothing is written if any file can't be saved.
+        self.check_write()?;
+        for include in self.updated_includes() {
+            if include.needs_write() {
+                include.write(false)?;
+            }
+        }
         match &self.path {
             Some(path) if path.is_dir() => {
                 eprintln!("Error! Path is directory: {}", path.display());
             }
             Some(path) => {
-                let mut file = File::create(path.as_os_str())?;
-                file.write_all(self.to_string().as_bytes())?;
+                let content: Vec<u8> = self.to_bytes();
+                if !force
+                    && fs::read(path).is_ok_and(|current| current == content)
+                {
+                    return Ok(());
+                }
+                if let Some(backup) = &self.backup {
+                    backup.create(&resolve_symlink(path))?;
+                }
+                write_atomic(path, &content)?;
             }
             None => {
                 eprintln!("Error! Can't write file, no filename given.");
@@ -97,15 +412,256 @@ impl Config {
         Ok(())
     }
 
+    // Check if file and all included files with changes can be written, without writing them.
+    fn check_write(&self) -> io::Result<()> {
+        if let (Some(path), Duplicates::Error) = (&self.path, self.duplicates)
+        {
+            if let Some((key, lines)) = self.find_duplicates().first() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Key '{key}' found multiple times in file \"{}\" at \
+                         lines: {}",
+                        path.display(),
+                        lines
+                            .iter()
+                            .map(|(number, _)| number.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                ));
+            }
+        }
+        if let Some((key, _)) =
+            self.data.iter().find(|(_, v)| !is_representable(v))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Value of key '{key}' can't be saved, quotation marks are \
+                     not allowed together with spaces or '#'"
+                ),
+            ));
+        }
+        for include in self.updated_includes() {
+            if include.needs_write() {
+                include.check_write()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Convert data to the exact content written to file, including the final line ending. Lines
+    // which are not changed keep their original bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut content: Vec<u8> =
+            self.render_lines().join(self.lineending.as_bytes());
+        if self.final_newline && !content.is_empty() {
+            content.extend_from_slice(self.lineending.as_bytes());
+        }
+        content
+    }
+
     // Read value of given key.
     pub fn get(&self, key: &str) -> Option<CompactString> {
         self.data.get(key).map(ToCompactString::to_compact_string)
     }
 
+    // Read value of key and convert it with parse, which returns None if it is not of the
+    // expected type.
+    fn get_typed<T>(
+        &self,
+        key: &str,
+        expected: &'static str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Result<T, ValueError> {
+        let value = self.data.get(key).ok_or_else(|| ValueError::Missing {
+            key: key.to_compact_string(),
+        })?;
+        parse(value).ok_or_else(|| ValueError::Invalid {
+            key: key.to_compact_string(),
+            value: value.clone(),
+            expected,
+        })
+    }
+
+    // Read value of key as boolean, the way RetroArch does. "true" and "1" are true, "false"
+    // and "0" are false.
+    pub fn get_bool(&self, key: &str) -> Result<bool, ValueError> {
+        self.get_typed(key, "'true' or 'false'", parse_bool)
+    }
+
+    // Read value of key as integer number.
+    pub fn get_int(&self, key: &str) -> Result<i64, ValueError> {
+        self.get_typed(key, "integer", |value| value.trim().parse().ok())
+    }
+
+    // Read value of key as floating point number.
+    pub fn get_float(&self, key: &str) -> Result<f64, ValueError> {
+        self.get_typed(key, "number", |value| value.trim().parse().ok())
+    }
+
+    // Read value of key as hexadecimal number, such as the color "ff5a5a5a". A leading "0x" is
+    // optional.
+    pub fn get_hex(&self, key: &str) -> Result<u32, ValueError> {
+        self.get_typed(key, "hexadecimal number", |value| {
+            let value = value.trim();
+            let digits = value
+                .strip_prefix("0x")
+                .or_else(|| value.strip_prefix("0X"))
+                .unwrap_or(value);
+            u32::from_str_radix(digits, 16).ok()
+        })
+    }
+
+    // Read value of key as path with a leading "~" replaced by users home directory. A leading
+    // ":" stands for the directory of the RetroArch executable, which is not known here, so it
+    // is left as it is. Use "get_path_value_in" to resolve it. Empty values are no path.
+    pub fn get_path_value(&self, key: &str) -> Result<PathBuf, ValueError> {
+        self.get_typed(key, "path", |value| {
+            if value.is_empty() {
+                return None;
+            }
+            Some(expand_home(Path::new(value)))
+        })
+    }
+
+    // Read value of key as path like "get_path_value", with a leading ":" replaced by app_dir,
+    // the directory of the RetroArch executable, such as ":/cores" or ":cores".
+    pub fn get_path_value_in(
+        &self,
+        key: &str,
+        app_dir: &Path,
+    ) -> Result<PathBuf, ValueError> {
+        let path: PathBuf = self.get_path_value(key)?;
+        match path.to_str().and_then(|text| text.strip_prefix(':')) {
+            Some(rest) => {
+                Ok(app_dir.join(rest.trim_start_matches(['/', '\\'])))
+            }
+            None => Ok(path),
+        }
+    }
+
+    // Set value of key to "true" or "false".
+    pub fn set_bool(
+        &mut self,
+        key: &str,
+        value: bool,
+    ) -> Option<CompactString> {
+        self.set(key, if value { "true" } else { "false" })
+    }
+
+    // Set value of key to an integer number.
+    pub fn set_int(&mut self, key: &str, value: i64) -> Option<CompactString> {
+        self.set(key, &value.to_string())
+    }
+
+    // Set value of key to a floating point number with 6 decimal places, like "1.000000".
+    pub fn set_float(
+        &mut self,
+        key: &str,
+        value: f64,
+    ) -> Option<CompactString> {
+        self.set(key, &format!("{value:.6}"))
+    }
+
+    // Set value of key to a hexadecimal number in lowercase without prefix, like "ff5a5a5a".
+    pub fn set_hex(&mut self, key: &str, value: u32) -> Option<CompactString> {
+        self.set(key, &format!("{value:x}"))
+    }
+
+    // Set value of key to a path. A path within users home directory is abbreviated with a
+    // leading "~", like RetroArch does.
+    pub fn set_path_value(
+        &mut self,
+        key: &str,
+        path: &Path,
+    ) -> Option<CompactString> {
+        let path: PathBuf = match std::env::var_os("HOME") {
+            Some(home) => match path.strip_prefix(&home) {
+                Ok(rest) => Path::new("~").join(rest),
+                Err(_) => path.to_path_buf(),
+            },
+            None => path.to_path_buf(),
+        };
+        self.set(key, &path.to_string_lossy())
+    }
+
+    // Switch boolean value of key between "true" and "false" and get the new value.
+    pub fn toggle(&mut self, key: &str) -> Result<bool, ValueError> {
+        let value: bool = !self.get_bool(key)?;
+        self.set_bool(key, value);
+        Ok(value)
+    }
+
+    // Add amount to numeric value of key, limited to optional lowest and highest number, and get
+    // the new value. Integers stay integers if amount is a whole number, otherwise the result is
+    // written as floating point number.
+    pub fn increment(
+        &mut self,
+        key: &str,
+        amount: f64,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Result<f64, ValueError> {
+        let clamp = |number: f64| {
+            number
+                .max(min.unwrap_or(f64::NEG_INFINITY))
+                .min(max.unwrap_or(f64::INFINITY))
+        };
+        match self.get_int(key) {
+            Ok(number) if amount.fract() == 0.0 => {
+                let result = clamp(number as f64 + amount).round() as i64;
+                self.set_int(key, result);
+                Ok(result as f64)
+            }
+            _ => {
+                let result = clamp(self.get_float(key)? + amount);
+                self.set_float(key, result);
+                Ok(result)
+            }
+        }
+    }
+
+    // Change value of key to the one following its current value in list and get the new value.
+    // After the last one the first is used again, which is also used if the key is missing or
+    // its value is not in list.
+    pub fn cycle(
+        &mut self,
+        key: &str,
+        values: &[&str],
+    ) -> Option<CompactString> {
+        let current = self.data.get(key);
+        let next: &str = match values
+            .iter()
+            .position(|value| current.is_some_and(|c| c == value))
+        {
+            Some(index) => values[(index + 1) % values.len()],
+            None => values.first()?,
+        };
+        self.set(key, next);
+        Some(next.to_compact_string())
+    }
+
     // Update existing or add missing key value pair to internal data.
     pub fn set(&mut self, key: &str, value: &str) -> Option<CompactString> {
-        self.data
-            .insert(key.to_compact_string(), value.to_compact_string())
+        self.insert_pair(key.to_compact_string(), value.to_compact_string())
+    }
+
+    // Insert key value pair into data and keep track if anything was changed.
+    fn insert_pair(
+        &mut self,
+        key: CompactString,
+        value: CompactString,
+    ) -> Option<CompactString> {
+        if self.data.get(&key) != Some(&value) {
+            self.modified = true;
+        }
+        if !self.data.contains_key(&key) {
+            self.added.retain(|added| *added != key);
+            self.added.push(key.clone());
+        }
+        self.data.insert(key, value)
     }
 
     // Search in value of existing key exact search string and change matching part with replace string.
@@ -118,7 +674,7 @@ impl Config {
         match self.data.get(key).map(ToCompactString::to_compact_string) {
             Some(value) => {
                 let new_value = value.replace(search, replace);
-                self.data.insert(
+                self.insert_pair(
                     key.to_compact_string(),
                     new_value.to_compact_string(),
                 )
@@ -127,13 +683,62 @@ impl Config {
         }
     }
 
+    // Search with regex in values of all keys matching the key regex and change each match with
+    // replacement text, which can refer to capture groups like "$1". Returns the number of
+    // changed values.
+    pub fn replace_regex(
+        &mut self,
+        key_pattern: &str,
+        value_pattern: &str,
+        replacement: &str,
+    ) -> usize {
+        let re_key = create_regex(key_pattern);
+        let re_value = create_regex(value_pattern);
+        let changes: Vec<(CompactString, CompactString)> = self
+            .data
+            .iter()
+            .filter(|(key, _)| re_key.is_match(key))
+            .map(|(key, value)| {
+                (
+                    key.clone(),
+                    re_value
+                        .replace_all(value, replacement)
+                        .to_compact_string(),
+                )
+            })
+            .filter(|(key, value)| self.data.get(key) != Some(value))
+            .collect();
+        let count: usize = changes.len();
+        for (key, value) in changes {
+            self.insert_pair(key, value);
+        }
+        count
+    }
+
     // Add missing key value pair or if existing, get its value.
     pub fn add(&mut self, key: &str, value: &str) -> Option<CompactString> {
         if self.data.contains_key(key) {
             self.data.get(key).map(ToCompactString::to_compact_string)
         } else {
-            self.data
-                .insert(key.to_compact_string(), value.to_compact_string())
+            self.insert_pair(
+                key.to_compact_string(),
+                value.to_compact_string(),
+            )
+        }
+    }
+
+    // Set existing key to its value in defaults. Missing keys are not added, as RetroArch uses
+    // the default for them anyway. Returns false if the key exists, but has no default.
+    pub fn reset(&mut self, key: &str, defaults: &Config) -> bool {
+        if !self.has_key(key) {
+            return true;
+        }
+        match defaults.get(key) {
+            Some(value) => {
+                self.set(key, &value);
+                true
+            }
+            None => false,
         }
     }
 
@@ -144,23 +749,194 @@ impl Config {
         value: &str,
     ) -> Option<CompactString> {
         self.data.remove(key);
+        self.lines.retain(|line| line.key() != Some(key));
         self.data.reverse();
         let pair = self
             .data
             .insert(key.to_compact_string(), value.to_compact_string());
         self.data.reverse();
+        self.modified = true;
         pair
     }
 
     // Remove key value pair from internal data by name of key.
     pub fn remove(&mut self, key: &str) -> Option<CompactString> {
-        self.data.remove(key)
+        let value = self.data.remove(key);
+        if value.is_some() {
+            self.modified = true;
+        }
+        value
+    }
+
+    // Remove all keys with names matching the regex pattern. Returns the number of removed keys.
+    pub fn remove_regex(&mut self, pattern: &str) -> usize {
+        let keys = self.find_by_key(pattern);
+        for (key, _) in &keys {
+            self.remove(key);
+        }
+        keys.len()
+    }
+
+    // Rename all keys with names matching the regex pattern to the name created from replacement
+    // text, which can refer to capture groups like "$1". See function "rename". A key is not
+    // renamed, if its new name is already taken by another key or is the new name of multiple
+    // keys, as one of the values would be lost. Returns a description of each skipped key.
+    pub fn rename_regex(
+        &mut self,
+        pattern: &str,
+        replacement: &str,
+    ) -> Vec<String> {
+        let re_key = create_regex(pattern);
+        let mut renames: Vec<(CompactString, CompactString)> = self
+            .find_by_key(pattern)
+            .into_iter()
+            .map(|(key, _)| {
+                let new_key = re_key.replace(&key, replacement);
+                let new_key = new_key.to_compact_string();
+                (key, new_key)
+            })
+            .filter(|(key, new_key)| key != new_key)
+            .collect();
+        let mut conflicts: Vec<String> = Vec::new();
+        let mut shared: HashSet<CompactString> = HashSet::new();
+        for (key, new_key) in &renames {
+            let others: Vec<&str> = renames
+                .iter()
+                .filter(|(other, target)| other != key && target == new_key)
+                .map(|(other, _)| other.as_str())
+                .collect();
+            if !others.is_empty() {
+                conflicts.push(format!(
+                    "key '{key}' not renamed, '{}' would be renamed to \
+                     '{new_key}' too",
+                    others.join("', '")
+                ));
+                shared.insert(new_key.clone());
+            }
+        }
+        renames.retain(|(_, new_key)| !shared.contains(new_key));
+
+        // A key with the new name is only replaced, if it is renamed itself. Skipped keys keep
+        // their name, so keys to be renamed to it are skipped too.
+        loop {
+            let taken: Option<usize> =
+                renames.iter().position(|(_, new_key)| {
+                    self.has_key(new_key)
+                        && !renames.iter().any(|(other, _)| other == new_key)
+                });
+            let Some(index) = taken else {
+                break;
+            };
+            let (key, new_key) = renames.remove(index);
+            conflicts.push(format!(
+                "key '{key}' not renamed, key '{new_key}' already exists"
+            ));
+        }
+
+        // Rename keys in an order, which never replaces a key still to be renamed.
+        while !renames.is_empty() {
+            let free: Option<usize> = renames
+                .iter()
+                .position(|(_, new_key)| !self.has_key(new_key));
+            let Some(index) = free else {
+                for (key, new_key) in renames.drain(..) {
+                    conflicts.push(format!(
+                        "key '{key}' not renamed, key '{new_key}' already exists"
+                    ));
+                }
+                break;
+            };
+            let (key, new_key) = renames.remove(index);
+            if let Err(conflict) = self.rename(&key, &new_key) {
+                conflicts.push(conflict);
+            }
+        }
+        conflicts
+    }
+
+    // Change name of an existing key, keeping its value and position. Returns the value of
+    // renamed key, if it exists. A key with the new name is never replaced, as its value would be
+    // lost, a description of the conflict is returned instead.
+    pub fn rename(
+        &mut self,
+        key: &str,
+        new_key: &str,
+    ) -> Result<Option<CompactString>, String> {
+        let Some(value) = self.get(key) else {
+            return Ok(None);
+        };
+        if key == new_key {
+            return Ok(Some(value));
+        }
+        if self.has_key(new_key) {
+            return Err(format!(
+                "key '{key}' not renamed, key '{new_key}' already exists"
+            ));
+        }
+        self.data = self
+            .data
+            .drain(..)
+            .map(|(k, v)| {
+                if k == key {
+                    (new_key.to_compact_string(), v)
+                } else {
+                    (k, v)
+                }
+            })
+            .collect();
+        self.lines.retain(|line| line.key() != Some(new_key));
+        if let Some(added) =
+            self.added.iter_mut().find(|added| added.as_str() == key)
+        {
+            *added = new_key.to_compact_string();
+        }
+        let formatted: Vec<u8> =
+            self.format_pair(new_key, &value).into_bytes();
+        if let Some(Line::Pair { key: k, raw, .. }) =
+            self.lines.iter_mut().find(|line| line.key() == Some(key))
+        {
+            match find_bytes(raw, key.as_bytes()) {
+                Some(start) => {
+                    raw.splice(start..start + key.len(), new_key.bytes());
+                }
+                None => *raw = formatted,
+            }
+            *k = new_key.to_compact_string();
+        }
+        self.modified = true;
+        Ok(Some(value))
     }
 
-    // Sort with standard algorithm the key value pairs in data.
+    // Sort with standard algorithm the key value pairs in data. Original lines of the file are
+    // replaced by one line for each key in sorted order, so comments and empty lines are dropped.
+    // Keys added afterwards are still placed relative to these lines.
     pub fn sort(&mut self) {
+        let unsorted: Vec<u8> = self.to_bytes();
         self.data.sort_keys();
         self.data.reverse();
+        let has_line: HashSet<&str> =
+            self.lines.iter().filter_map(Line::key).collect();
+        let sorted: Vec<Line> = self
+            .data
+            .iter()
+            .rev()
+            .filter(|(key, _)| {
+                has_line.contains(key.as_str())
+                    || !self
+                        .includes
+                        .iter()
+                        .any(|include| include.data.contains_key(*key))
+            })
+            .map(|(key, value)| Line::Pair {
+                key: key.clone(),
+                value: value.clone(),
+                raw: format_pair_string(key, value, 0).into_bytes(),
+            })
+            .collect();
+        self.lines = sorted;
+        if self.to_bytes() != unsorted {
+            self.modified = true;
+        }
     }
 
     // Update internal data by parsing a slice in cfg text data format.
@@ -170,11 +946,47 @@ impl Config {
         }
     }
 
-    // Update or add key value pairs provided by another Config, without changing path.
+    // Update or add key value pairs provided by another Config, without changing path. New keys
+    // count as added in the order they appear in the other config, from top to bottom.
     pub fn insert_from_config(&mut self, config: &Config) {
+        let new_keys: Vec<CompactString> = config
+            .data
+            .keys()
+            .rev()
+            .filter(|key| !self.data.contains_key(*key))
+            .cloned()
+            .collect();
         for (key, value) in &config.data {
-            self.data
-                .insert(key.to_compact_string(), value.to_compact_string());
+            self.insert_pair(
+                key.to_compact_string(),
+                value.to_compact_string(),
+            );
+        }
+        self.added.retain(|key| !new_keys.contains(key));
+        self.added.extend(new_keys);
+    }
+
+    // Remove all key value pairs which have the same value in base config. Keys missing in base
+    // are kept, so only the differences to base remain.
+    pub fn remove_equal(&mut self, base: &Config) {
+        self.remove_same(base, |_, value, other| value == other);
+    }
+
+    // Remove all key value pairs which have a value in base config, that is the same according
+    // to function same, which gets the key, value and value in base.
+    pub fn remove_same(
+        &mut self,
+        base: &Config,
+        same: impl Fn(&str, &str, &str) -> bool,
+    ) {
+        let count: usize = self.data.len();
+        self.data.retain(|key, value| {
+            base.data
+                .get(key)
+                .is_none_or(|other| !same(key, value, other))
+        });
+        if self.data.len() != count {
+            self.modified = true;
         }
     }
 
@@ -184,7 +996,7 @@ impl Config {
         map: IndexMap<CompactString, CompactString>,
     ) {
         for (key, value) in map {
-            self.data.insert(key, value);
+            self.insert_pair(key, value);
         }
     }
 
@@ -281,12 +1093,20 @@ impl Config {
     // Read file at current path and key and values to data. If key exists multiple times, the
     // value for first encounter of key have priority.
     pub fn read_file(&mut self) {
+        self.unreadable = true;
         match &self.path {
-            Some(file) if file.is_file() => {
-                for line in read_lines_reverse(file.as_os_str()) {
-                    self.insert_line(line.as_str());
+            Some(file) if file.is_file() => match fs::read(file) {
+                Ok(content) => {
+                    self.read_bytes(&content);
+                    self.unreadable = false;
                 }
-            }
+                Err(error) => {
+                    eprintln!(
+                        "Warning! Cant read config data. {error}: \"{}\"",
+                        file.display()
+                    );
+                }
+            },
             Some(file) if file.is_dir() => {
                 eprintln!(
                     "Warning! Cant read config data. Path is a directory: \"{}\"",
@@ -305,21 +1125,153 @@ impl Config {
         }
     }
 
+    // Parse entire content of a cfg file. Every line is remembered for writing it back later and
+    // the key value pairs are added to data. Line ending of the text is used for writing too.
+    pub fn read_text(&mut self, text: &str) {
+        self.read_bytes(text.as_bytes());
+    }
+
+    // Parse content of a cfg file like "read_text". Bytes which are not valid UTF-8 are replaced
+    // for parsing only, each line keeps its original bytes for writing.
+    pub fn read_bytes(&mut self, content: &[u8]) {
+        let (lines, lineending) = split_lines(content);
+        if let Some(lineending) = lineending {
+            self.lineending = lineending;
+        }
+        self.final_newline =
+            content.ends_with(b"\n") || content.ends_with(b"\r");
+        let texts: Vec<String> = lines
+            .iter()
+            .map(|line| String::from_utf8_lossy(line).to_string())
+            .collect();
+        self.lines = lines
+            .iter()
+            .zip(&texts)
+            .map(|(line, text)| match Config::parse_line(text) {
+                Some((key, value)) => Line::Pair {
+                    key,
+                    value,
+                    raw: line.to_vec(),
+                },
+                None => Line::Other(line.to_vec()),
+            })
+            .collect();
+        for text in texts.iter().rev() {
+            self.insert_line(text);
+        }
+        self.added.clear();
+        self.modified = false;
+    }
+
+    // Convert data to lines of text in the order they are written to file. Lines read from file
+    // stay untouched, unless the value of its key was changed or the key was removed. Any
+    // additional lines of same key further down are kept as they are. New keys are inserted as
+    // set by placement.
+    fn render_lines(&self) -> Vec<Vec<u8>> {
+        let mut known_keys: HashSet<&str> =
+            self.lines.iter().filter_map(Line::key).collect();
+        for include in &self.includes {
+            known_keys.extend(include.data.keys().map(CompactString::as_str));
+        }
+        let mut new_keys: Vec<&str> = self
+            .data
+            .keys()
+            .rev()
+            .map(CompactString::as_str)
+            .filter(|key| !known_keys.contains(key))
+            .collect();
+
+        // Lines of the file together with the key, if it is the first line of the key.
+        let mut seen_keys: HashSet<&str> = HashSet::new();
+        let mut rendered: Vec<(Option<&str>, Vec<u8>)> = Vec::new();
+        for line in &self.lines {
+            match line {
+                Line::Pair { key, value, raw } => {
+                    let Some(current) = self.data.get(key) else {
+                        continue;
+                    };
+                    let shadowed = !seen_keys.insert(key);
+                    if shadowed && self.duplicates == Duplicates::KeepFirst {
+                        continue;
+                    }
+                    if shadowed {
+                        rendered.push((None, raw.clone()));
+                    } else if current == value && self.style == 0 {
+                        rendered.push((Some(key), raw.clone()));
+                    } else {
+                        let mut changed: Vec<u8> =
+                            self.format_pair(key, current).into_bytes();
+                        if let Some(comment) = trailing_comment(raw) {
+                            changed.extend_from_slice(comment);
+                        }
+                        rendered.push((Some(key), changed));
+                    }
+                }
+                Line::Other(raw) => rendered.push((None, raw.clone())),
+            }
+        }
+
+        let new_line = |key: &str| {
+            let value = self.data.get(key).cloned().unwrap_or_default();
+            self.format_pair(key, &value).into_bytes()
+        };
+        match &self.placement {
+            Placement::Top | Placement::After(_) => {
+                let index: usize = match &self.placement {
+                    Placement::After(anchor) => rendered
+                        .iter()
+                        .position(|(key, _)| *key == Some(anchor.as_str()))
+                        .map_or(0, |index| index + 1),
+                    _ => 0,
+                };
+                rendered.splice(
+                    index..index,
+                    new_keys.iter().map(|key| (Some(*key), new_line(key))),
+                );
+            }
+            // Keys are appended in the order they were added.
+            Placement::Bottom => {
+                new_keys.sort_by_key(|key| {
+                    self.added.iter().position(|added| added == key)
+                });
+                rendered.extend(
+                    new_keys.iter().map(|key| (Some(*key), new_line(key))),
+                );
+            }
+            Placement::Sorted => {
+                new_keys.sort_unstable();
+                for key in new_keys {
+                    let index: usize = rendered
+                        .iter()
+                        .position(|(other, _)| other.is_some_and(|o| o > key))
+                        .or_else(|| {
+                            rendered
+                                .iter()
+                                .rposition(|(other, _)| other.is_some())
+                                .map(|index| index + 1)
+                        })
+                        .unwrap_or(rendered.len());
+                    rendered.insert(index, (Some(key), new_line(key)));
+                }
+            }
+        }
+        rendered.into_iter().map(|(_, line)| line).collect()
+    }
+
     // Parse a slice of a line and add key value pair to data.
     pub fn insert_line(&mut self, line: &str) {
         if let Some((key, value)) = Config::parse_line(line) {
-            self.data
-                .insert(key.to_compact_string(), value.to_compact_string());
+            self.insert_pair(key, value);
         }
     }
 
-    // Parse a slice of a cfg formatted text with keys and values.
+    // Parse a slice of a cfg formatted text with keys and values. Comments are ignored.
     pub fn parse_line(line: &str) -> Option<(CompactString, CompactString)> {
+        if line.trim_start().starts_with('#') {
+            return None;
+        }
         line.split_once('=').map(|(key, value)| {
-            (
-                key.trim().to_compact_string(),
-                value.trim().trim_matches('"').to_compact_string(),
-            )
+            (key.trim().to_compact_string(), parse_value(value))
         })
     }
 
@@ -331,12 +1283,28 @@ impl Config {
         println!("{}", format_value_string(value, self.style));
     }
 
+    // Print every key found on multiple lines, each occurrence with its line number.
+    pub fn print_duplicates(&self) {
+        for (key, lines) in self.find_duplicates() {
+            for (number, value) in lines {
+                println!("{number}: {}", self.format_pair(&key, &value));
+            }
+        }
+    }
+
+    // Print path of the file defining the key, followed by a colon and without a newline.
+    pub fn print_origin(&self, key: &str) {
+        let origin = self.origin(key).unwrap_or_default();
+        print!("{}: ", origin.display());
+    }
+
     pub fn print_pair(&self, key: &str, value: &str) {
-        println!(
-            "{} = \"{}\"",
-            format_key_string(key, self.style),
-            format_value_string(value, self.style)
-        );
+        println!("{}", self.format_pair(key, value));
+    }
+
+    // Create a line of text from key and value in cfg format, styled with current preset.
+    pub fn format_pair(&self, key: &str, value: &str) -> String {
+        format_pair_string(key, value, self.style)
     }
 
     pub fn print_keys_list(&self) {
@@ -350,18 +1318,178 @@ impl Config {
     }
 }
 
-// Get lines from a file. Lines are in reverse order for priority reasons. Don't forget to reverse
-// the lines after work is done.
-fn read_lines_reverse<P>(filename: P) -> RevLines<File>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename).expect("Can't open file.");
-    RevLines::new(io::BufReader::new(file))
-        .expect("Could not read file line by line.")
+impl Line {
+    // Get name of the key, if this line is a key value pair.
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            Line::Pair { key, .. } => Some(key.as_str()),
+            Line::Other(_) => None,
+        }
+    }
+}
+
+// Write content to a temporary file next to the target, flush it to disk and rename it over the
+// target. The target is never left half written, even if the program stops in the middle of it.
+// Symbolic links are followed to replace the real file. Permissions of an existing target are
+// kept and on Unix also its ownership, if allowed.
+fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let target: PathBuf = resolve_symlink(path);
+    let directory: PathBuf = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temp: PathBuf =
+        directory.join(format!(".{name}.reconf-{}.tmp", std::process::id()));
+
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+        file.write_all(content)?;
+        if let Ok(metadata) = fs::metadata(&target) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                let _ = std::os::unix::fs::chown(
+                    &temp,
+                    Some(metadata.uid()),
+                    Some(metadata.gid()),
+                );
+            }
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &target)?;
+        #[cfg(unix)]
+        if let Ok(dir) = File::open(&directory) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+// Follow symbolic links until the real file. Links to a missing file resolve to the path of the
+// file they point to, so it can be created.
+fn resolve_symlink(path: &Path) -> PathBuf {
+    let mut current: PathBuf = path.to_path_buf();
+    // Limit depth for the case of circular links.
+    for _ in 0..40 {
+        match fs::read_link(&current) {
+            Ok(link) => {
+                current = match current.parent() {
+                    Some(dir) => normalize_path(&dir.join(link)),
+                    None => link,
+                };
+            }
+            Err(_) => break,
+        }
+    }
+    current
+}
+
+// Get the path of an "#include" line as it is written, without surrounding quotation marks.
+fn parse_include(line: &str) -> Option<PathBuf> {
+    let path = line
+        .trim()
+        .strip_prefix("#include")?
+        .trim()
+        .trim_matches('"');
+    if path.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
+// Convert text to boolean the way RetroArch does, "true" and "1" are true, "false" and "0" are
+// false. Anything else is no boolean.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
 }
 
-fn create_regex<S: AsRef<str>>(pattern: S) -> Regex {
+// Replace a leading tilde "~" with users home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+// Split content into lines without the line ending characters. Lines end at "\n" and a "\r" in
+// front of it is removed from each line on its own, so mixed line endings are handled too. Only
+// content without any "\n" is split at "\r". The line ending for writing is returned too, if the
+// content has any.
+fn split_lines(content: &[u8]) -> (Vec<&[u8]>, Option<&'static str>) {
+    let lineending = if find_bytes(content, b"\r\n").is_some() {
+        Some("\r\n")
+    } else if content.contains(&b'\n') {
+        Some("\n")
+    } else if content.contains(&b'\r') {
+        Some("\r")
+    } else {
+        None
+    };
+    let mut lines: Vec<&[u8]> = match lineending {
+        Some("\r") => content.split(|&byte| byte == b'\r').collect(),
+        Some(_) => content
+            .split(|&byte| byte == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .collect(),
+        None => vec![content],
+    };
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    (lines, lineending)
+}
+
+// Find position of the first occurrence of needle in bytes.
+fn find_bytes(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    bytes
+        .windows(needle.len().max(1))
+        .position(|window| window == needle)
+}
+
+// Get the comment after the value of a key value pair line, together with the whitespace in
+// front of it, such as "  # comment". Returns nothing, if the line has no such comment.
+fn trailing_comment(raw: &[u8]) -> Option<&[u8]> {
+    let equal = raw.iter().position(|&byte| byte == b'=')?;
+    let after = &raw[equal + 1..];
+    let start = after.iter().position(|byte| !byte.is_ascii_whitespace())?;
+    let value = &after[start..];
+    let rest: &[u8] = match value.strip_prefix(b"\"") {
+        Some(quoted) => {
+            let end = quoted.iter().position(|&byte| byte == b'"')?;
+            &quoted[end + 1..]
+        }
+        None => {
+            let end = value
+                .iter()
+                .position(|&byte| byte.is_ascii_whitespace() || byte == b'#')
+                .unwrap_or(value.len());
+            &value[end..]
+        }
+    };
+    let comment = rest.iter().position(|byte| !byte.is_ascii_whitespace())?;
+    if rest[comment] == b'#' {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+pub fn create_regex<S: AsRef<str>>(pattern: S) -> Regex {
     match Regex::new(pattern.as_ref()) {
         Ok(regex) => regex,
         Err(error) => {
@@ -433,15 +1561,276 @@ pub fn format_key_string(key: &str, style: u8) -> colored::ColoredString {
 
 pub fn format_value_string(value: &str, style: u8) -> colored::ColoredString {
     match style {
-        1 => value.trim().italic().yellow(),
-        2 => value.trim().bold().green(),
-        3 => value.trim().bright_magenta(),
-        4 => value.trim().italic(),
-        5 => value.trim().italic().blue(),
-        6 => value.trim().dimmed().yellow(),
-        7 => value.trim().bright_red(),
-        8 => value.trim().bold().white(),
-        9 => value.trim().dimmed().white(),
-        _ => value.trim().clear(),
+        1 => value.italic().yellow(),
+        2 => value.bold().green(),
+        3 => value.bright_magenta(),
+        4 => value.italic(),
+        5 => value.italic().blue(),
+        6 => value.dimmed().yellow(),
+        7 => value.bright_red(),
+        8 => value.bold().white(),
+        9 => value.dimmed().white(),
+        _ => value.clear(),
+    }
+}
+
+// Create a line of text from key and value in cfg format, styled with given preset. Values are
+// enclosed in quotation marks, unless they contain one. See function "is_representable".
+pub fn format_pair_string(key: &str, value: &str, style: u8) -> String {
+    if value.contains('"') {
+        format!(
+            "{} = {}",
+            format_key_string(key, style),
+            format_value_string(value, style)
+        )
+    } else {
+        format!(
+            "{} = \"{}\"",
+            format_key_string(key, style),
+            format_value_string(value, style)
+        )
+    }
+}
+
+// Extract value from the text after the equal sign, the way RetroArch reads it. A value starting
+// with a quotation mark ends at the next one and anything after it is ignored. RetroArch has no
+// escape sequences, so quoted values cannot contain quotation marks. Other values end at the
+// first whitespace or start of a comment and may contain quotation marks.
+pub fn parse_value(text: &str) -> CompactString {
+    let text = text.trim_start();
+    let value = match text.strip_prefix('"') {
+        Some(quoted) => match quoted.split_once('"') {
+            Some((value, _)) => value,
+            None => quoted.trim_end(),
+        },
+        None => text
+            .split('#')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .next()
+            .unwrap_or_default(),
+    };
+    value.to_compact_string()
+}
+
+// Check if value can be written, so it is read back unchanged by RetroArch. Values without
+// quotation marks are always enclosed in quotation marks. Values with quotation marks must be
+// written without surrounding quotes, so they cannot contain whitespace or a comment character
+// and cannot start with a quotation mark.
+pub fn is_representable(value: &str) -> bool {
+    !value.contains('"')
+        || !(value.starts_with('"')
+            || value.contains('#')
+            || value.contains(char::is_whitespace))
+}
+
+// Convert text to a quoted JSON string with all necessary characters escaped.
+pub fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                json.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(pairs: &[(&str, &str)]) -> Config {
+        let mut config = Config::new();
+        for (key, value) in pairs {
+            config.set(key, value);
+        }
+        config
+    }
+
+    fn invalid(key: &str, value: &str, expected: &'static str) -> ValueError {
+        ValueError::Invalid {
+            key: key.into(),
+            value: value.into(),
+            expected,
+        }
+    }
+
+    #[test]
+    fn get_bool() {
+        let config = config(&[
+            ("a", "true"),
+            ("b", "1"),
+            ("c", "false"),
+            ("d", "0"),
+            ("e", "yes"),
+        ]);
+        assert_eq!(config.get_bool("a"), Ok(true));
+        assert_eq!(config.get_bool("b"), Ok(true));
+        assert_eq!(config.get_bool("c"), Ok(false));
+        assert_eq!(config.get_bool("d"), Ok(false));
+        assert_eq!(
+            config.get_bool("e"),
+            Err(invalid("e", "yes", "'true' or 'false'"))
+        );
+        assert_eq!(
+            config.get_bool("f"),
+            Err(ValueError::Missing { key: "f".into() })
+        );
+    }
+
+    #[test]
+    fn get_numbers() {
+        let config =
+            config(&[("int", "-42"), ("float", "1.5"), ("text", "abc")]);
+        assert_eq!(config.get_int("int"), Ok(-42));
+        assert_eq!(
+            config.get_int("float"),
+            Err(invalid("float", "1.5", "integer"))
+        );
+        assert_eq!(config.get_float("float"), Ok(1.5));
+        assert_eq!(config.get_float("int"), Ok(-42.0));
+        assert_eq!(
+            config.get_float("text"),
+            Err(invalid("text", "abc", "number"))
+        );
+    }
+
+    #[test]
+    fn get_hex() {
+        let config = config(&[
+            ("color", "ff5a5a5a"),
+            ("lower", "0xff"),
+            ("upper", "0X1A"),
+            ("prefix", "0x"),
+            ("text", "zz"),
+        ]);
+        assert_eq!(config.get_hex("color"), Ok(0xff5a_5a5a));
+        assert_eq!(config.get_hex("lower"), Ok(0xff));
+        assert_eq!(config.get_hex("upper"), Ok(0x1a));
+        assert_eq!(
+            config.get_hex("prefix"),
+            Err(invalid("prefix", "0x", "hexadecimal number"))
+        );
+        assert_eq!(
+            config.get_hex("text"),
+            Err(invalid("text", "zz", "hexadecimal number"))
+        );
+    }
+
+    #[test]
+    fn get_path_value() {
+        let config = config(&[
+            ("empty", ""),
+            ("absolute", "/usr/lib/cores"),
+            ("application", ":/cores"),
+            ("relative", ":cores"),
+        ]);
+        assert_eq!(
+            config.get_path_value("empty"),
+            Err(invalid("empty", "", "path"))
+        );
+        assert_eq!(
+            config.get_path_value("absolute"),
+            Ok(PathBuf::from("/usr/lib/cores"))
+        );
+        assert_eq!(
+            config.get_path_value("application"),
+            Ok(PathBuf::from(":/cores"))
+        );
+        let app_dir = Path::new("/opt/retroarch");
+        assert_eq!(
+            config.get_path_value_in("application", app_dir),
+            Ok(PathBuf::from("/opt/retroarch/cores"))
+        );
+        assert_eq!(
+            config.get_path_value_in("relative", app_dir),
+            Ok(PathBuf::from("/opt/retroarch/cores"))
+        );
+        assert_eq!(
+            config.get_path_value_in("absolute", app_dir),
+            Ok(PathBuf::from("/usr/lib/cores"))
+        );
+        assert_eq!(
+            config.get_path_value_in("empty", app_dir),
+            Err(invalid("empty", "", "path"))
+        );
+    }
+
+    #[test]
+    fn setters() {
+        let mut config = Config::new();
+        config.set_bool("bool", false);
+        config.set_int("int", -3);
+        config.set_float("float", 1.5);
+        config.set_hex("hex", 0xff5a_5a5a);
+        config.set_path_value("path", Path::new("/usr/lib/cores"));
+        assert_eq!(config.get("bool").as_deref(), Some("false"));
+        assert_eq!(config.get("int").as_deref(), Some("-3"));
+        assert_eq!(config.get("float").as_deref(), Some("1.500000"));
+        assert_eq!(config.get("hex").as_deref(), Some("ff5a5a5a"));
+        assert_eq!(config.get("path").as_deref(), Some("/usr/lib/cores"));
+    }
+
+    #[test]
+    fn toggle() {
+        let mut config = config(&[("one", "1"), ("text", "on")]);
+        assert_eq!(config.toggle("one"), Ok(false));
+        assert_eq!(config.get("one").as_deref(), Some("false"));
+        assert_eq!(
+            config.toggle("text"),
+            Err(invalid("text", "on", "'true' or 'false'"))
+        );
+        assert_eq!(config.get("text").as_deref(), Some("on"));
+    }
+
+    #[test]
+    fn rename_keeps_existing_key() {
+        let mut config = config(&[("a", "1"), ("b", "2")]);
+        assert!(config.rename("a", "b").is_err());
+        assert_eq!(config.get("a").as_deref(), Some("1"));
+        assert_eq!(config.get("b").as_deref(), Some("2"));
+        assert_eq!(config.rename("a", "c"), Ok(Some("1".into())));
+        assert_eq!(config.rename("missing", "d"), Ok(None));
+    }
+
+    #[test]
+    fn rename_regex_conflicts() {
+        let mut config = config(&[
+            ("c1_x", "1"),
+            ("c2_x", "2"),
+            ("d_y", "3"),
+            ("y", "old"),
+            ("e_z", "4"),
+        ]);
+        let conflicts = config.rename_regex("^(c\\d|d|e)_", "");
+        assert_eq!(conflicts.len(), 3);
+        assert_eq!(config.get("c1_x").as_deref(), Some("1"));
+        assert_eq!(config.get("c2_x").as_deref(), Some("2"));
+        assert_eq!(config.get("y").as_deref(), Some("old"));
+        assert_eq!(config.get("z").as_deref(), Some("4"));
+        assert!(!config.has_key("x"));
+    }
+
+    #[test]
+    fn value_error_message() {
+        assert_eq!(
+            ValueError::Missing { key: "a".into() }.to_string(),
+            "key 'a' not found"
+        );
+        assert_eq!(
+            invalid("a", "x", "integer").to_string(),
+            "key 'a' has invalid value \"x\", expected integer"
+        );
     }
 }
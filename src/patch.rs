@@ -0,0 +1,354 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::parser::Config;
+
+// https://crates.io/crates/compact_str/
+use compact_str::CompactString;
+use compact_str::ToCompactString;
+
+// Patch files list one operation per line, in the format written by '--diff --format unified':
+//
+//     -key = "old"    remove key, if current value is "old"
+//     -key            remove key, whatever its value is
+//     +key = "new"    add missing key, if a line for same key is directly above this one, then
+//                     the value is changed instead and the condition of that line is used
+//     >key = "other"  rename existing key to "other", keeping its value
+//
+// Lines starting with "---" or "+++", comments starting with "#" and empty lines are ignored.
+
+// Requirement on current value of key before an operation is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Any,
+    Missing,
+    Equals(CompactString),
+}
+
+// A single change to apply to a config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Set {
+        key: CompactString,
+        value: CompactString,
+        condition: Condition,
+    },
+    Delete {
+        key: CompactString,
+        condition: Condition,
+    },
+    Rename {
+        key: CompactString,
+        new_key: CompactString,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Patch {
+    pub path: Option<PathBuf>,
+    pub operations: Vec<Operation>,
+}
+
+impl Patch {
+    // Read and parse a patch file.
+    pub fn load(path: &Path) -> io::Result<Patch> {
+        let text = fs::read_to_string(path)?;
+        let mut patch = Patch::parse(&text).map_err(|message| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {message}", path.display()),
+            )
+        })?;
+        patch.path = Some(path.to_path_buf());
+        Ok(patch)
+    }
+
+    // Parse text in patch format. Lines which are not recognized are reported with their number.
+    pub fn parse(text: &str) -> Result<Patch, String> {
+        let mut operations: Vec<Operation> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("---")
+                || line.starts_with("+++")
+            {
+                continue;
+            }
+            let mut chars = line.chars();
+            let prefix = chars.next();
+            let rest = chars.as_str();
+            let pair = Config::parse_line(rest);
+            let operation = match (prefix, pair) {
+                (Some('-'), Some((key, value))) => Operation::Delete {
+                    key,
+                    condition: Condition::Equals(value),
+                },
+                (Some('-'), None) if !rest.trim().is_empty() => {
+                    Operation::Delete {
+                        key: rest.trim().to_compact_string(),
+                        condition: Condition::Any,
+                    }
+                }
+                (Some('+'), Some((key, value))) => match operations.last() {
+                    Some(Operation::Delete {
+                        key: previous,
+                        condition,
+                    }) if *previous == key => {
+                        let condition = condition.clone();
+                        operations.pop();
+                        Operation::Set {
+                            key,
+                            value,
+                            condition,
+                        }
+                    }
+                    _ => Operation::Set {
+                        key,
+                        value,
+                        condition: Condition::Missing,
+                    },
+                },
+                (Some('>'), Some((key, new_key))) => {
+                    Operation::Rename { key, new_key }
+                }
+                _ => {
+                    return Err(format!(
+                        "line {}: invalid patch operation: {line}",
+                        index + 1
+                    ))
+                }
+            };
+            operations.push(operation);
+        }
+        Ok(Patch {
+            path: None,
+            operations,
+        })
+    }
+
+    // Apply all operations to config in order. Operations with a failed condition are skipped
+    // and a message for each one is returned. Operations which are already in effect, such as
+    // adding a key with same value, are not reported.
+    pub fn apply(&self, config: &mut Config) -> Vec<String> {
+        let mut conflicts: Vec<String> = Vec::new();
+        for operation in &self.operations {
+            match operation {
+                Operation::Set {
+                    key,
+                    value,
+                    condition,
+                } => {
+                    if config.get(key).as_ref() == Some(value) {
+                        continue;
+                    }
+                    match check(config, key, condition) {
+                        Ok(()) => {
+                            config.set(key, value);
+                        }
+                        Err(message) => conflicts.push(message),
+                    }
+                }
+                Operation::Delete { key, condition } => {
+                    if !config.has_key(key) {
+                        continue;
+                    }
+                    match check(config, key, condition) {
+                        Ok(()) => {
+                            config.remove(key);
+                        }
+                        Err(message) => conflicts.push(message),
+                    }
+                }
+                Operation::Rename { key, new_key } => {
+                    if !config.has_key(key) && config.has_key(new_key) {
+                        continue;
+                    }
+                    if !config.has_key(key) {
+                        conflicts.push(format!(
+                            "key '{key}' not found for renaming"
+                        ));
+                    } else if let Err(message) = config.rename(key, new_key) {
+                        conflicts.push(message);
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+// Verify condition against current value of key in config.
+fn check(
+    config: &Config,
+    key: &str,
+    condition: &Condition,
+) -> Result<(), String> {
+    match (condition, config.get(key)) {
+        (Condition::Any, _) | (Condition::Missing, None) => Ok(()),
+        (Condition::Missing, Some(current)) => Err(format!(
+            "key '{key}' expected to be missing, found \"{current}\""
+        )),
+        (Condition::Equals(expected), Some(current))
+            if *expected == current =>
+        {
+            Ok(())
+        }
+        (Condition::Equals(expected), Some(current)) => Err(format!(
+            "key '{key}' expected \"{expected}\", found \"{current}\""
+        )),
+        (Condition::Equals(expected), None) => Err(format!(
+            "key '{key}' expected \"{expected}\", but is missing"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::Diff;
+
+    fn config(pairs: &[(&str, &str)]) -> Config {
+        let mut config = Config::new();
+        for (key, value) in pairs {
+            config.set(key, value);
+        }
+        config
+    }
+
+    #[test]
+    fn parse_operations() {
+        let patch = Patch::parse(
+            "--- a.cfg\n+++ b.cfg\n# comment\n\n\
+             -a = \"1\"\n-b\n+c = \"3\"\n>d = \"e\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            patch.operations,
+            [
+                Operation::Delete {
+                    key: "a".into(),
+                    condition: Condition::Equals("1".into()),
+                },
+                Operation::Delete {
+                    key: "b".into(),
+                    condition: Condition::Any,
+                },
+                Operation::Set {
+                    key: "c".into(),
+                    value: "3".into(),
+                    condition: Condition::Missing,
+                },
+                Operation::Rename {
+                    key: "d".into(),
+                    new_key: "e".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Patch::parse("-a = \"1\"\nkey = \"value\"\n").unwrap_err(),
+            "line 2: invalid patch operation: key = \"value\""
+        );
+        assert_eq!(
+            Patch::parse("-\n").unwrap_err(),
+            "line 1: invalid patch operation: -"
+        );
+        assert_eq!(
+            Patch::parse("+a\n").unwrap_err(),
+            "line 1: invalid patch operation: +a"
+        );
+        assert_eq!(
+            Patch::parse(">a\n").unwrap_err(),
+            "line 1: invalid patch operation: >a"
+        );
+    }
+
+    #[test]
+    fn parse_merges_changed_key() {
+        let patch = Patch::parse("-a = \"1\"\n+a = \"2\"\n").unwrap();
+        assert_eq!(
+            patch.operations,
+            [Operation::Set {
+                key: "a".into(),
+                value: "2".into(),
+                condition: Condition::Equals("1".into()),
+            }]
+        );
+        let patch = Patch::parse("-a\n+a = \"2\"\n").unwrap();
+        assert_eq!(
+            patch.operations,
+            [Operation::Set {
+                key: "a".into(),
+                value: "2".into(),
+                condition: Condition::Any,
+            }]
+        );
+        // Only a line for same key directly above is merged.
+        let patch = Patch::parse("-a = \"1\"\n+b = \"2\"\n").unwrap();
+        assert_eq!(patch.operations.len(), 2);
+    }
+
+    #[test]
+    fn apply_conflicts() {
+        let patch = Patch::parse(
+            "+a = \"new\"\n-b = \"2\"\n+b = \"new\"\n-c = \"3\"\n",
+        )
+        .unwrap();
+        let mut config = config(&[("a", "1"), ("b", "other"), ("c", "3")]);
+        let conflicts = patch.apply(&mut config);
+        assert_eq!(
+            conflicts,
+            [
+                "key 'a' expected to be missing, found \"1\"",
+                "key 'b' expected \"2\", found \"other\"",
+            ]
+        );
+        assert_eq!(config.get("a").as_deref(), Some("1"));
+        assert_eq!(config.get("b").as_deref(), Some("other"));
+        assert_eq!(config.get("c"), None);
+
+        let patch = Patch::parse("-d = \"4\"\n+d = \"new\"\n").unwrap();
+        let mut config = Config::new();
+        assert_eq!(
+            patch.apply(&mut config),
+            ["key 'd' expected \"4\", but is missing"]
+        );
+        assert_eq!(config.get("d"), None);
+    }
+
+    #[test]
+    fn apply_twice() {
+        let patch = Patch::parse(
+            "+a = \"1\"\n-b = \"2\"\n+b = \"3\"\n-c\n>d = \"e\"\n",
+        )
+        .unwrap();
+        let mut config = config(&[("b", "2"), ("c", "x"), ("d", "4")]);
+        assert!(patch.apply(&mut config).is_empty());
+        let applied = config.to_string();
+        assert!(patch.apply(&mut config).is_empty());
+        assert_eq!(config.to_string(), applied);
+        assert_eq!(config.get("a").as_deref(), Some("1"));
+        assert_eq!(config.get("b").as_deref(), Some("3"));
+        assert_eq!(config.get("c"), None);
+        assert_eq!(config.get("d"), None);
+        assert_eq!(config.get("e").as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn unified_diff_round_trip() {
+        let old = config(&[("a", "1"), ("b", "2"), ("c", "say \"hi\"")]);
+        let new = config(&[("b", "3"), ("c", "say \"hi\""), ("d", "4")]);
+        let text = Diff::new(&old, &new).to_unified(0);
+        let patch = Patch::parse(&text).unwrap();
+        let mut config = old.clone();
+        assert!(patch.apply(&mut config).is_empty());
+        for key in ["a", "b", "c", "d"] {
+            assert_eq!(config.get(key), new.get(key), "key {key}");
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::parser::{
+    create_regex, format_key_string, format_value_string, parse_bool, Config,
+};
+
+// https://docs.rs/indexmap/latest/indexmap/
+use indexmap::IndexMap;
+
+// https://crates.io/crates/compact_str/
+use compact_str::CompactString;
+use compact_str::ToCompactString;
+
+// Schema of "retroarch.cfg" shipped with the program. See the file itself for its format.
+const BUNDLED: &str = include_str!("schema.txt");
+
+// Kind of value a key accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Bool,
+    Int,
+    Float,
+    Path,
+    Enum,
+    String,
+}
+
+impl FromStr for ValueType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "bool" => Ok(ValueType::Bool),
+            "int" => Ok(ValueType::Int),
+            "float" => Ok(ValueType::Float),
+            "path" => Ok(ValueType::Path),
+            "enum" => Ok(ValueType::Enum),
+            "string" => Ok(ValueType::String),
+            _ => Err(format!(
+                "unknown type '{name}', expected 'bool', 'int', 'float', \
+                 'path', 'enum' or 'string'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ValueType::Bool => "bool",
+            ValueType::Int => "int",
+            ValueType::Float => "float",
+            ValueType::Path => "path",
+            ValueType::Enum => "enum",
+            ValueType::String => "string",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// Everything known about a single key.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub key: CompactString,
+    pub kind: ValueType,
+    // Lowest and highest allowed number for types "int" and "float".
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    // Allowed values for type "enum".
+    pub values: Vec<CompactString>,
+    pub default: CompactString,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    // All entries by key name, in order of the schema file.
+    pub entries: IndexMap<CompactString, Entry>,
+}
+
+impl Entry {
+    // Check if value is of the type and within the range of this entry. The error describes the
+    // expected values.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let valid = match self.kind {
+            ValueType::Bool => parse_bool(value).is_some(),
+            ValueType::Int => value
+                .parse::<i64>()
+                .is_ok_and(|number| self.in_range(number as f64)),
+            ValueType::Float => value
+                .parse::<f64>()
+                .is_ok_and(|number| self.in_range(number)),
+            ValueType::Enum => self.values.iter().any(|v| v.as_str() == value),
+            ValueType::Path | ValueType::String => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("expected {}", self.allowed()))
+        }
+    }
+
+    // Describe values accepted by this entry, such as "integer from 0 to 4".
+    pub fn allowed(&self) -> String {
+        let range = match (self.min, self.max) {
+            (Some(min), Some(max)) => format!(" from {min} to {max}"),
+            (Some(min), None) => format!(" from {min}"),
+            (None, Some(max)) => format!(" up to {max}"),
+            (None, None) => String::new(),
+        };
+        match self.kind {
+            ValueType::Bool => "'true' or 'false'".to_string(),
+            ValueType::Int => format!("integer{range}"),
+            ValueType::Float => format!("number{range}"),
+            ValueType::Enum => format!("one of: {}", self.values.join(", ")),
+            ValueType::Path => "path to a file or directory".to_string(),
+            ValueType::String => "any text".to_string(),
+        }
+    }
+
+    // Document the entry with its description, type, allowed values and default, followed by
+    // the current value in each config that has a path. For merged configs the file defining
+    // the value is shown instead.
+    pub fn explain(&self, configs: &[Config], style: u8) -> String {
+        let quoted =
+            |value: &str| format!("\"{}\"", format_value_string(value, style));
+        let mut lines: Vec<String> = vec![
+            format_key_string(&self.key, style).to_string(),
+            format!("  {}", self.description),
+            format!("  type:     {}", self.kind),
+            format!("  allowed:  {}", self.allowed()),
+            format!("  default:  {}", quoted(&self.default)),
+        ];
+        for config in configs.iter().filter(|config| config.path.is_some()) {
+            match config.get(&self.key) {
+                Some(value) => lines.push(format!(
+                    "  {}: {}",
+                    config.origin(&self.key).unwrap_or_default().display(),
+                    quoted(&value)
+                )),
+                None => lines
+                    .push(format!("  {}: not set", config.path_to_string())),
+            }
+        }
+        lines.join("\n")
+    }
+
+    // Check if two values mean the same for the type of this entry, such as "1" and "true" for
+    // booleans or "0.0" and "0.000000" for numbers. Values not of the type are compared as text.
+    pub fn same_value(&self, a: &str, b: &str) -> bool {
+        let same = match self.kind {
+            ValueType::Bool => {
+                parse_bool(a).zip(parse_bool(b)).map(|(a, b)| a == b)
+            }
+            ValueType::Int => a
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .zip(b.trim().parse::<i64>().ok())
+                .map(|(a, b)| a == b),
+            ValueType::Float => a
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .zip(b.trim().parse::<f64>().ok())
+                .map(|(a, b)| a == b),
+            ValueType::Path | ValueType::Enum | ValueType::String => None,
+        };
+        same.unwrap_or(a == b)
+    }
+
+    fn in_range(&self, number: f64) -> bool {
+        self.min.is_none_or(|min| number >= min)
+            && self.max.is_none_or(|max| number <= max)
+    }
+}
+
+impl Schema {
+    // Schema of "retroarch.cfg" shipped with the program.
+    pub fn bundled() -> Schema {
+        Schema::parse(BUNDLED).expect("Bundled schema must be valid.")
+    }
+
+    // Read and parse a schema file.
+    pub fn load(path: &Path) -> io::Result<Schema> {
+        let text = fs::read_to_string(path)?;
+        Schema::parse(&text).map_err(|message| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {message}", path.display()),
+            )
+        })
+    }
+
+    // Parse text in schema format, one key per line as "key|type|range|default|description".
+    // Lines which are not recognized are reported with their number.
+    pub fn parse(text: &str) -> Result<Schema, String> {
+        let mut entries: IndexMap<CompactString, Entry> = IndexMap::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_entry(line)
+                .map_err(|message| format!("line {}: {message}", index + 1))?;
+            entries.insert(entry.key.clone(), entry);
+        }
+        Ok(Schema { entries })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.get(key)
+    }
+
+    // Create a config with the default value of every entry.
+    pub fn defaults(&self) -> Config {
+        let mut config = Config::new();
+        for entry in self.entries.values().rev() {
+            config.set(&entry.key, &entry.default);
+        }
+        config
+    }
+
+    // Get all entries with key names matching the regex pattern, in order of the schema.
+    pub fn find(&self, pattern: &str) -> Vec<&Entry> {
+        let regex = create_regex(pattern);
+        self.entries
+            .values()
+            .filter(|entry| regex.is_match(&entry.key))
+            .collect()
+    }
+
+    // Check if key is known and value is valid for it. Unknown keys are reported together with
+    // the most similar known key, if any.
+    pub fn validate(&self, key: &str, value: &str) -> Result<(), String> {
+        match self.get(key) {
+            Some(entry) => entry.check(value).map_err(|expected| {
+                format!(
+                    "invalid value \"{value}\" for key '{key}', {expected}"
+                )
+            }),
+            None => match self.suggest(key) {
+                Some(similar) => Err(format!(
+                    "unknown key '{key}', did you mean '{similar}'?"
+                )),
+                None => Err(format!("unknown key '{key}'")),
+            },
+        }
+    }
+
+    // Find the known key with the smallest edit distance to a misspelled key. Keys which differ
+    // too much are not considered similar.
+    pub fn suggest(&self, key: &str) -> Option<&str> {
+        let limit = (key.chars().count() / 4 + 1).min(3);
+        self.entries
+            .keys()
+            .map(|known| (levenshtein(key, known), known))
+            .filter(|(distance, _)| *distance <= limit)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, known)| known.as_str())
+    }
+}
+
+fn parse_entry(line: &str) -> Result<Entry, String> {
+    let fields: Vec<&str> = line.splitn(5, '|').collect();
+    let [key, kind, range, default, description] = fields[..] else {
+        return Err(format!("expected 5 fields separated by '|': {line}"));
+    };
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return Err(format!("invalid key name '{key}'"));
+    }
+    let kind: ValueType = kind.trim().parse()?;
+    let range = range.trim();
+    let mut entry = Entry {
+        key: key.to_compact_string(),
+        kind,
+        min: None,
+        max: None,
+        values: Vec::new(),
+        default: default.trim().to_compact_string(),
+        description: description.trim().to_string(),
+    };
+    match kind {
+        ValueType::Int | ValueType::Float if !range.is_empty() => {
+            let Some((min, max)) = range.split_once("..") else {
+                return Err(format!("invalid range '{range}'"));
+            };
+            let number = |text: &str| -> Result<Option<f64>, String> {
+                match text.trim() {
+                    "" => Ok(None),
+                    text => text
+                        .parse::<f64>()
+                        .map(Some)
+                        .map_err(|_| format!("invalid range '{range}'")),
+                }
+            };
+            entry.min = number(min)?;
+            entry.max = number(max)?;
+        }
+        ValueType::Enum => {
+            entry.values = range
+                .split(',')
+                .map(|value| value.trim().to_compact_string())
+                .collect();
+        }
+        _ => {}
+    }
+    if !entry.default.is_empty() {
+        entry.check(&entry.default).map_err(|expected| {
+            format!("invalid default \"{}\", {expected}", entry.default)
+        })?;
+    }
+    Ok(entry)
+}
+
+// Number of single character insertions, deletions or substitutions to turn one text into the
+// other.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, char_a) in a.chars().enumerate() {
+        let mut current: Vec<usize> = vec![i + 1];
+        for (j, char_b) in b.iter().enumerate() {
+            let cost = usize::from(char_a != *char_b);
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_value_by_type() {
+        let schema = Schema::bundled();
+        let vsync = schema.get("video_vsync").unwrap();
+        assert!(vsync.same_value("1", "true"));
+        assert!(vsync.same_value("false", "0"));
+        assert!(!vsync.same_value("1", "false"));
+        let volume = schema.get("audio_volume").unwrap();
+        assert!(volume.same_value("0.0", "0.000000"));
+        assert!(!volume.same_value("0.5", "0.000000"));
+        assert!(!volume.same_value("loud", "0.000000"));
+    }
+}
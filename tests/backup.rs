@@ -0,0 +1,70 @@
+// Backups made by '--write' must be complete: every write gets its own backup and files with
+// the same name in a shared '--backup-dir' don't touch each others backups.
+
+mod common;
+
+use std::fs;
+
+use common::{reconf, TempDir};
+
+#[test]
+fn same_name_in_backup_dir() {
+    let dir = TempDir::new("same-name-in-backup-dir");
+    let backups = dir.path().join("backups");
+    let first = dir.file("c1/roms.cfg", "system = \"first\"\n");
+    let second = dir.file("c2/roms.cfg", "system = \"second\"\n");
+
+    let backup_dir = backups.to_str().unwrap();
+    for path in [&first, &second] {
+        let output = reconf(
+            &[
+                path.to_str().unwrap(),
+                "-s",
+                "system",
+                "changed",
+                "-w",
+                "--backup-dir",
+                backup_dir,
+                "--backup-keep",
+                "1",
+            ],
+            "",
+        );
+        assert!(output.status.success());
+    }
+    // Rotation of the second file must not remove the backup of the first one.
+    assert_eq!(fs::read_dir(&backups).unwrap().count(), 2);
+
+    for (path, expected) in [(&first, "first"), (&second, "second")] {
+        let output = reconf(
+            &[
+                path.to_str().unwrap(),
+                "--restore",
+                "1",
+                "-w",
+                "--backup-dir",
+                backup_dir,
+            ],
+            "",
+        );
+        assert!(output.status.success());
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            format!("system = \"{expected}\"\n")
+        );
+    }
+}
+
+#[test]
+fn backup_for_every_write() {
+    let dir = TempDir::new("backup-for-every-write");
+    let path = dir.file("retroarch.cfg", "a = \"1\"\n");
+    let file = path.to_str().unwrap();
+    for value in ["2", "3"] {
+        let output = reconf(&[file, "-s", "a", value, "-w", "--backup"], "");
+        assert!(output.status.success());
+    }
+    let output = reconf(&[file, "--restore", "1", "-w"], "");
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a = \"2\"\n");
+}
@@ -0,0 +1,25 @@
+// Positions reported by '--check' count bytes of the file, also after bytes which are not valid
+// UTF-8.
+
+mod common;
+
+use common::{reconf, TempDir};
+
+#[test]
+fn columns_after_invalid_bytes() {
+    let dir = TempDir::new("columns-after-invalid-bytes");
+    let path =
+        dir.file("invalid.cfg", b"\xffa = \"x\" junk\n\xff\xfeb = 1 2\n");
+    let output = reconf(&[path.to_str().unwrap(), "--check"], "");
+    let file = path.to_str().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{file}:1:1: error: invalid UTF-8 byte\n\
+             {file}:1:10: error: trailing text after closing quotation mark\n\
+             {file}:2:1: error: invalid UTF-8 byte\n\
+             {file}:2:7: warning: value not enclosed in quotation marks\n\
+             {file}:2:9: error: trailing text after value\n"
+        )
+    );
+}
@@ -0,0 +1,81 @@
+// Writing a file back keeps every line that was not changed byte for byte, even if it is not
+// valid UTF-8.
+
+mod common;
+
+use std::fs;
+
+use common::{reconf, TempDir};
+
+#[test]
+fn invalid_utf8_is_kept() {
+    let dir = TempDir::new("invalid-utf8-is-kept");
+    let path =
+        dir.file("latin1.cfg", b"# caf\xe9\na = \"1\"\nb = \"x\xff\"\n");
+    let output = reconf(&[path.to_str().unwrap(), "-s", "a", "2", "-w"], "");
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(&path).unwrap(),
+        b"# caf\xe9\na = \"2\"\nb = \"x\xff\"\n"
+    );
+}
+
+#[test]
+fn mixed_line_endings_are_split() {
+    let dir = TempDir::new("mixed-line-endings-are-split");
+    let path = dir.file("mixed.cfg", b"a = \"1\"\r\nb = \"2\"\nc = \"3\"\r\n");
+    let output = reconf(&[path.to_str().unwrap(), "-g", "b"], "");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2\n");
+    let output = reconf(&[path.to_str().unwrap(), "-g", "c"], "");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+}
+
+#[test]
+fn inline_comment_is_kept_on_change() {
+    let dir = TempDir::new("inline-comment-is-kept-on-change");
+    let path = dir.file(
+        "inline.cfg",
+        b"a = \"1\"   # quoted\nb = 2 # unquoted\nc = \"3\" junk\n",
+    );
+    let output = reconf(
+        &[
+            path.to_str().unwrap(),
+            "-s",
+            "a",
+            "5",
+            "-s",
+            "b",
+            "6",
+            "-s",
+            "c",
+            "7",
+            "-w",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "a = \"5\"   # quoted\nb = \"6\" # unquoted\nc = \"7\"\n"
+    );
+}
+
+#[test]
+fn unchanged_file_keeps_mtime() {
+    let dir = TempDir::new("unchanged-file-keeps-mtime");
+    let include = dir.file("included.cfg", b"x = \"1\"\n");
+    let path =
+        dir.file("including.cfg", b"#include \"included.cfg\"\na = \"1\"\n");
+    let old = std::time::SystemTime::UNIX_EPOCH;
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    let output =
+        reconf(&[path.to_str().unwrap(), "-I", "-s", "x", "12", "-w"], "");
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&include).unwrap(), "x = \"12\"\n");
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), old);
+}
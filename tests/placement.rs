@@ -0,0 +1,169 @@
+// Position of keys which are not in the file yet for each '--placement' policy. Keys at the
+// bottom are appended in the order they were added, also without any existing lines.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{reconf, TempDir};
+
+// Add keys "c", "a" and "e" in this order to a file with "b" and "d" or to a new file, and get
+// the keys of the written file from top to bottom.
+fn place(policy: &str, existing: bool) -> Vec<String> {
+    let dir = TempDir::new(&format!(
+        "placement-{}-{existing}",
+        policy.replace(':', "-")
+    ));
+    let path = dir.path().join("retroarch.cfg");
+    if existing {
+        fs::write(&path, "b = \"2\"\nd = \"4\"\n").unwrap();
+    }
+    let output = reconf(
+        &[
+            path.to_str().unwrap(),
+            "-s",
+            "c",
+            "3",
+            "-s",
+            "a",
+            "1",
+            "-s",
+            "e",
+            "5",
+            "--placement",
+            policy,
+            "-w",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    keys(&path)
+}
+
+fn keys(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| line.split(" = ").next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn top() {
+    assert_eq!(place("top", true), ["e", "a", "c", "b", "d"]);
+    assert_eq!(place("top", false), ["e", "a", "c"]);
+}
+
+#[test]
+fn bottom() {
+    assert_eq!(place("bottom", true), ["b", "d", "c", "a", "e"]);
+    assert_eq!(place("bottom", false), ["c", "a", "e"]);
+}
+
+#[test]
+fn sorted() {
+    assert_eq!(place("sorted", true), ["a", "b", "c", "d", "e"]);
+    assert_eq!(place("sorted", false), ["a", "c", "e"]);
+}
+
+#[test]
+fn after_key() {
+    assert_eq!(place("after:b", true), ["b", "e", "a", "c", "d"]);
+    assert_eq!(place("after:b", false), ["e", "a", "c"]);
+}
+
+#[test]
+fn bottom_after_sort() {
+    let dir = TempDir::new("placement-bottom-after-sort");
+    let path = dir.file("sort.cfg", "d = \"4\"\nb = \"2\"\n");
+    let output = reconf(
+        &[
+            path.to_str().unwrap(),
+            "--sort",
+            "-s",
+            "c",
+            "3",
+            "--placement",
+            "bottom",
+            "-w",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(keys(&path), ["b", "d", "c"]);
+}
+
+#[test]
+fn bottom_export() {
+    let dir = TempDir::new("placement-bottom-export");
+    let source = dir.file("source.cfg", "x = \"1\"\ny = \"2\"\nz = \"3\"\n");
+    let export = dir.path().join("export.cfg");
+    let output = reconf(
+        &[
+            source.to_str().unwrap(),
+            "--export",
+            export.to_str().unwrap(),
+            "--placement",
+            "bottom",
+            "-w",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(keys(&export), ["x", "y", "z"]);
+}
+
+#[test]
+fn bottom_below_comment() {
+    let dir = TempDir::new("placement-bottom-below-comment");
+    let path = dir.file("comment.cfg", "# comment\n");
+    let output = reconf(
+        &[
+            path.to_str().unwrap(),
+            "-s",
+            "c",
+            "3",
+            "-s",
+            "a",
+            "1",
+            "--placement",
+            "bottom",
+            "-w",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "# comment\nc = \"3\"\na = \"1\"\n"
+    );
+}
+
+#[test]
+fn stdin_keeps_order() {
+    for (policy, existing) in [
+        ("top", false),
+        ("top", true),
+        ("bottom", false),
+        ("bottom", true),
+    ] {
+        let dir =
+            TempDir::new(&format!("placement-stdin-{policy}-{existing}"));
+        let path = dir.path().join("stdin.cfg");
+        if existing {
+            fs::write(&path, "b = \"2\"\n").unwrap();
+        }
+        let output = reconf(
+            &[path.to_str().unwrap(), "-i", "--placement", policy, "-w"],
+            "x = \"1\"\ny = \"2\"\nz = \"3\"\n",
+        );
+        assert!(output.status.success());
+        let expected: &[&str] = match (policy, existing) {
+            ("top", true) => &["x", "y", "z", "b"],
+            ("bottom", true) => &["b", "x", "y", "z"],
+            _ => &["x", "y", "z"],
+        };
+        assert_eq!(keys(&path), expected);
+    }
+}
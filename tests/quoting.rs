@@ -0,0 +1,128 @@
+// Values with quotation marks and other tricky characters must be read the way RetroArch reads
+// them and survive a round trip.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{reconf, TempDir};
+
+fn get(path: &Path, key: &str) -> String {
+    let output = reconf(&[path.to_str().unwrap(), "-g", key], "");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+const TRICKY: &str = "\
+# comment with \"quotes\" and key = \"value\"
+quoted = \"value with spaces\"
+hash = \"path/to/#file.glsl\"
+empty = \"\"
+padded = \"  two spaces  \"
+unquoted = value
+unquoted_comment = value # comment
+quote_inside = nick\"name
+trailing = \"value\" garbage
+comment_after = \"value\" # comment
+no_closing = \"value
+";
+
+#[test]
+fn untouched_file_is_unchanged() {
+    let dir = TempDir::new("untouched-file-is-unchanged");
+    let path = dir.file("untouched.cfg", TRICKY);
+    let output = reconf(&[path.to_str().unwrap(), "-w", "--force"], "");
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&path).unwrap(), TRICKY);
+}
+
+#[test]
+fn values_are_read_like_retroarch() {
+    let dir = TempDir::new("values-are-read-like-retroarch");
+    let path = dir.file("read.cfg", TRICKY);
+    assert_eq!(get(&path, "quoted"), "value with spaces\n");
+    assert_eq!(get(&path, "hash"), "path/to/#file.glsl\n");
+    assert_eq!(get(&path, "empty"), "\n");
+    assert_eq!(get(&path, "padded"), "  two spaces  \n");
+    assert_eq!(get(&path, "unquoted"), "value\n");
+    assert_eq!(get(&path, "unquoted_comment"), "value\n");
+    assert_eq!(get(&path, "quote_inside"), "nick\"name\n");
+    assert_eq!(get(&path, "trailing"), "value\n");
+    assert_eq!(get(&path, "comment_after"), "value\n");
+    assert_eq!(get(&path, "no_closing"), "value\n");
+}
+
+#[test]
+fn set_value_with_quote_round_trips() {
+    let dir = TempDir::new("set-value-with-quote-round-trips");
+    let path = dir.file("set_quote.cfg", "netplay_nickname = \"old\"\n");
+    let file = path.to_str().unwrap();
+    let output =
+        reconf(&[file, "-s", "netplay_nickname", "The\"Kid", "-w"], "");
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "netplay_nickname = The\"Kid\n"
+    );
+    assert_eq!(get(&path, "netplay_nickname"), "The\"Kid\n");
+}
+
+#[test]
+fn set_value_with_hash_and_spaces_round_trips() {
+    let dir = TempDir::new("set-value-with-hash-and-spaces-round-trips");
+    let path = dir.file("set_hash.cfg", "");
+    let file = path.to_str().unwrap();
+    let value = " shaders/#1 crt.slangp ";
+    let output = reconf(&[file, "-s", "video_shader", value, "-w"], "");
+    assert!(output.status.success());
+    assert_eq!(get(&path, "video_shader"), format!("{value}\n"));
+}
+
+#[test]
+fn set_unrepresentable_value_fails() {
+    let dir = TempDir::new("set-unrepresentable-value-fails");
+    let path = dir.file("set_invalid.cfg", "title = \"old\"\n");
+    let file = path.to_str().unwrap();
+    for value in ["say \"hi\"", "\"quoted", "a\"b#c"] {
+        let output = reconf(&[file, "-s", "title", value, "-w"], "");
+        assert!(!output.status.success(), "accepted value: {value}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "title = \"old\"\n");
+    }
+}
+
+#[test]
+fn stdin_values_are_read_like_file_content() {
+    let dir = TempDir::new("stdin-values-are-read-like-file-content");
+    let path = dir.file("stdin.cfg", "");
+    let file = path.to_str().unwrap();
+    let stdin = "\
+title = \"Super \"Mario\" Bros\"
+nick = a\"b
+plain=value
+spaces = \"  x  \"
+";
+    let output = reconf(&[file, "-i", "-w"], stdin);
+    assert!(output.status.success());
+    assert_eq!(get(&path, "title"), "Super \n");
+    assert_eq!(get(&path, "nick"), "a\"b\n");
+    assert_eq!(get(&path, "plain"), "value\n");
+    assert_eq!(get(&path, "spaces"), "  x  \n");
+}
+
+#[test]
+fn changed_line_keeps_other_lines() {
+    let dir = TempDir::new("changed-line-keeps-other-lines");
+    let path = dir.file("changed.cfg", TRICKY);
+    let file = path.to_str().unwrap();
+    let output = reconf(&[file, "-s", "quoted", "new \"value\"", "-w"], "");
+    assert!(!output.status.success());
+    let output = reconf(&[file, "-s", "quoted", "new value", "-w"], "");
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        TRICKY.replace(
+            "quoted = \"value with spaces\"",
+            "quoted = \"new value\""
+        )
+    );
+}